@@ -10,6 +10,10 @@ use syn::{
     Attribute, ItemTrait, PatTuple, PatTupleStruct, Path, ReturnType, TraitItemFn, Visibility,
 };
 
+/// How often the generated `update_<property>` helpers try before giving up with the last
+/// `ValueChanged`
+const UPDATE_ATTEMPTS: usize = 32;
+
 #[derive(FromMeta, Debug)]
 pub struct GenCodeInput {
     name: String,
@@ -21,6 +25,8 @@ pub struct GenCodeInput {
 pub struct Input {
     #[darling(default)]
     name: Option<String>,
+    #[darling(default)]
+    deprecated: Option<String>,
 }
 
 #[derive(Debug)]
@@ -38,7 +44,7 @@ pub fn expand(
         Ok(e) => e,
         Err(v) => return v.into(),
     };
-    let _functions = match _trait
+    let _fns = _trait
         .items
         .into_iter()
         .map(|item| match item {
@@ -46,6 +52,13 @@ pub fn expand(
             _ => None,
         })
         .flatten()
+        .collect::<Vec<_>>();
+    let load_all = match generate_load_all(&_fns, &_trait.ident, &args.name, &_trait.vis, args.blocking) {
+        Ok(e) => e,
+        Err(e) => return e.into(),
+    };
+    let _functions = match _fns
+        .into_iter()
         .map(|item| {
             generate_for_function(item, &_trait.ident, &args.name, &_trait.vis, args.blocking)
         })
@@ -61,7 +74,7 @@ pub fn expand(
         Err(e) => return e.into(),
     };
 
-   quote::quote!( #schema #(#_functions)* ).into()
+   quote::quote!( #schema #load_all #(#_functions)* ).into()
    
 }
 
@@ -105,6 +118,94 @@ pub fn generate_schema(
     Ok(stream)
 }
 
+/// Generates `<Ident>Values`, holding every property of the trait, and `load_all` filling it
+/// from a single `get_all` call, along with the delayed-apply calls
+pub fn generate_load_all(
+    functions: &[TraitItemFn],
+    ident: &syn::Ident,
+    schema_name: &String,
+    vis: &syn::Visibility,
+    blocking: bool,
+) -> Result<proc_macro2::TokenStream, proc_macro2::TokenStream> {
+    let values_ident = format_ident!("{}Values", ident);
+    let (_async, _await) = blocking
+        .then(|| (Default::default(), Default::default()))
+        .unwrap_or((quote::quote!(async), quote::quote!(.await)));
+
+    let mut fields = vec![];
+    let mut reads = vec![];
+    for _fn in functions {
+        let FunctionType::Property = check_attrs_for_function(&_fn.attrs)? else {
+            continue;
+        };
+        let input = match Input::from_attributes(&_fn.attrs) {
+            Ok(e) => e,
+            Err(r) => return Err(r.write_errors()),
+        };
+        let field = &_fn.sig.ident;
+        let name = input.name.unwrap_or(field.to_string());
+        let deprecated = input
+            .deprecated
+            .map(|note| quote::quote!(#[deprecated(note = #note)]))
+            .unwrap_or_default();
+        let target_ty = match &_fn.sig.output {
+            ReturnType::Default => quote::quote!(()),
+            ReturnType::Type(_, ty) => quote::quote!( #ty ),
+        };
+
+        fields.push(quote::quote!(
+            #deprecated
+            pub #field: ::core::option::Option<#target_ty>
+        ));
+        reads.push(quote::quote!(
+            #field: match values.remove(#name) {
+                Some((false, value)) => Some(
+                    <#target_ty as ::core::convert::TryFrom<::zbus::zvariant::OwnedValue>>::try_from(value)
+                        .map_err(|_| ::zbus::Error::Variant(::zbus::zvariant::Error::IncorrectType))?,
+                ),
+                _ => None,
+            }
+        ));
+    }
+
+    Ok(quote::quote!(
+        /// Current value of every property, `None` where it is null
+        #[derive(Debug, Clone)]
+        #vis struct #values_ident {
+            #(#fields),*
+        }
+
+        impl #ident {
+            #[allow(deprecated)]
+            pub #_async fn load_all(&self) -> ::gludconfig::error::DaemonResult<#values_ident> {
+                let mut values = self.property_proxy.call::<_, _, ::std::collections::HashMap<String, (bool, ::zbus::zvariant::OwnedValue)>>("get_all", &(#schema_name,))#_await?;
+                Ok(#values_ident {
+                    #(#reads),*
+                })
+            }
+
+            /// Stages the writes made through this connection until `apply` or `revert`
+            pub #_async fn delay(&self) -> ::gludconfig::error::DaemonResult<()> {
+                Ok(self.property_proxy.call("delay", &(#schema_name,))#_await?)
+            }
+
+            /// Writes everything staged since `delay` at once
+            pub #_async fn apply(&self) -> ::gludconfig::error::DaemonResult<()> {
+                Ok(self.property_proxy.call("apply", &(#schema_name,))#_await?)
+            }
+
+            /// Drops everything staged since `delay`, returns whether there was anything staged
+            pub #_async fn revert(&self) -> ::gludconfig::error::DaemonResult<bool> {
+                Ok(self.property_proxy.call("revert", &(#schema_name,))#_await?)
+            }
+
+            pub #_async fn has_unapplied(&self) -> ::gludconfig::error::DaemonResult<bool> {
+                Ok(self.property_proxy.call("has_unapplied", &(#schema_name,))#_await?)
+            }
+        }
+    ))
+}
+
 pub fn generate_for_function(
     mut _fn: TraitItemFn,
     schema_ident: &syn::Ident,
@@ -129,6 +230,10 @@ pub fn generate_for_function(
     let ident = &_fn.sig.ident;
     let name = input.name.unwrap_or(ident.to_string());
     let generics = &_fn.sig.generics;
+    let deprecated = input
+        .deprecated
+        .map(|note| quote::quote!(#[deprecated(note = #note)]))
+        .unwrap_or_default();
     let target_ty = match &_fn.sig.output {
         ReturnType::Default => quote::quote!(()),
         ReturnType::Type(_, ty) => quote::quote!( #ty ),
@@ -149,17 +254,20 @@ pub fn generate_for_function(
 
             let stream = quote::quote!(
                 impl #schema_ident {
-                    pub #_async fn #emit_trigger_ident #generics(&self, value: #target_ty) -> ::zbus::Result<()> {
+                    #deprecated
+                    pub #_async fn #emit_trigger_ident #generics(&self, value: #target_ty) -> ::gludconfig::error::DaemonResult<()> {
                         let value = ::zbus::zvariant::Value::new(value).to_owned();
-                        self.trigger_proxy.call::<_, _, ()>("trigger", &(#schema_name, #name, value))#_await
+                        Ok(self.trigger_proxy.call::<_, _, ()>("trigger", &(#schema_name, #name, value))#_await?)
                     }
 
+                    #deprecated
                     pub #_async fn #listen_trigger_ident #generics(&self) -> ::zbus::Result<#trigger_occur_ty> {
                         self.trigger_proxy.receive_signal_with_args("trigger_invoked", &[(0, #schema_name), (1, #name)])#_await
                     }
 
-                    pub #_async fn #info_ident #generics(&self) -> ::zbus::Result<(String, ::zbus::zvariant::OwnedSignature)> {
-                        self.trigger_proxy.call("metadata", &(#schema_name, #name))#_await
+                    #deprecated
+                    pub #_async fn #info_ident #generics(&self) -> ::gludconfig::error::DaemonResult<(String, ::zbus::zvariant::OwnedSignature)> {
+                        Ok(self.trigger_proxy.call("metadata", &(#schema_name, #name))#_await?)
                     }
                 }
             );
@@ -168,9 +276,15 @@ pub fn generate_for_function(
         }
         FunctionType::Property => {
             let set_ident = format_ident!("set_{}", ident);
+            let set_if_ident = format_ident!("set_{}_if", ident);
+            let update_ident = format_ident!("update_{}", ident);
             let reset_ident = format_ident!("reset_{}", ident);
             let info_ident = format_ident!("info_{}", ident);
             let change_ident = format_ident!("{}_changed", ident);
+            let enabled_change_ident = format_ident!("{}_enabled_changed", ident);
+            let backoff = blocking
+                .then(|| quote::quote!(::gludconfig::retry::backoff_blocking))
+                .unwrap_or(quote::quote!(::gludconfig::retry::backoff));
 
             let change_ty = blocking
                 .then(|| quote::quote!(::zbus::blocking::SignalIterator<'static>))
@@ -178,21 +292,65 @@ pub fn generate_for_function(
 
             let stream = quote::quote!(
                 impl #schema_ident {
-                    pub #_async fn #info_ident #generics(&self) -> ::zbus::Result<(bool, String, String, String, bool, ::zbus::zvariant::OwnedSignature, (bool, ::zbus::zvariant::OwnedValue))> {
-                        self.property_proxy.call::<_, _, (bool, String, String, String, bool, ::zbus::zvariant::OwnedSignature, (bool, ::zbus::zvariant::OwnedValue))>("metadata", &(#schema_name, #name))#_await
+                    #deprecated
+                    pub #_async fn #info_ident #generics(&self) -> ::gludconfig::error::DaemonResult<::gludconfig::property::PropertyInfo> {
+                        Ok(self.property_proxy.call::<_, _, ::gludconfig::property::PropertyInfo>("metadata_v2", &(#schema_name, #name, ::gludconfig::locale::current_locale()))#_await?)
                     }
 
+                    #deprecated
                     pub #_async fn #change_ident #generics(&self) -> ::zbus::Result<#change_ty> {
                         self.property_proxy.receive_signal_with_args("property_changed", &[(0, #schema_name), (1, #name)])#_await
                     }
-                    pub #_async fn #set_ident #generics(&self, value: ::core::option::Option<#target_ty>) -> ::zbus::Result<()> {
-                        let (is_null, value) = match value {
-                            None => (true, ::zbus::zvariant::Value::from(true).to_owned()),
-                            Some(value) => (false, ::zbus::zvariant::Value::from(value).to_owned()),
+                    #deprecated
+                    pub #_async fn #enabled_change_ident #generics(&self) -> ::zbus::Result<#change_ty> {
+                        self.property_proxy.receive_signal_with_args("enabled_changed", &[(0, #schema_name), (1, #name)])#_await
+                    }
+                    #deprecated
+                    pub #_async fn #set_ident #generics(&self, value: ::core::option::Option<#target_ty>) -> ::gludconfig::error::DaemonResult<()> {
+                        let value = ::gludconfig::value::Nullable::wrap(value);
+                        Ok(self.property_proxy.call::<_, _, ()>("set", &(#schema_name, #name, value))#_await?)
+                    }
+                    /// Sets the value only if the property still holds `expected`, fails with
+                    /// `DaemonError::ValueChanged` otherwise
+                    #deprecated
+                    pub #_async fn #set_if_ident #generics(&self, expected: ::core::option::Option<#target_ty>, value: ::core::option::Option<#target_ty>) -> ::gludconfig::error::DaemonResult<()> {
+                        let (expected, value) = (::gludconfig::value::Nullable::wrap(expected), ::gludconfig::value::Nullable::wrap(value));
+                        Ok(self.property_proxy.call::<_, _, ()>("set_if", &(#schema_name, #name, expected, value))#_await?)
+                    }
+                    /// Replaces the value with `update(current)`, reading it again and retrying
+                    /// whenever another client changed it in between, see `gludconfig::retry`.
+                    /// Returns the written value, or `DaemonError::ValueChanged` if it kept
+                    /// changing for every attempt.
+                    #deprecated
+                    pub #_async fn #update_ident #generics(
+                        &self,
+                        mut update: impl FnMut(::core::option::Option<#target_ty>) -> ::core::option::Option<#target_ty>,
+                    ) -> ::gludconfig::error::DaemonResult<::core::option::Option<#target_ty>> {
+                        let decode = |value: ::gludconfig::value::Nullable| -> ::gludconfig::error::DaemonResult<::core::option::Option<#target_ty>> {
+                            let value: ::core::option::Option<::zbus::zvariant::OwnedValue> = value.into();
+                            Ok(value
+                                .map(<#target_ty as ::core::convert::TryFrom<::zbus::zvariant::OwnedValue>>::try_from)
+                                .transpose()
+                                .map_err(|_| ::zbus::Error::Variant(::zbus::zvariant::Error::IncorrectType))?)
                         };
-                        Ok(self.property_proxy.call::<_, _, ()>("set", &(#schema_name, #name, (is_null, value)))#_await?)
+                        let mut attempts = 1;
+                        loop {
+                            let mut values = self.property_proxy.call::<_, _, ::std::collections::HashMap<String, ::gludconfig::value::Nullable>>("get_many", &(#schema_name, ::std::vec![#name]))#_await?;
+                            let expected = values.remove(#name).ok_or(::zbus::Error::MissingField)?;
+                            let new = ::gludconfig::value::Nullable::wrap(update(decode(expected.clone())?));
+                            let written = self.property_proxy.call::<_, _, ()>("set_if", &(#schema_name, #name, expected, new.clone()))#_await;
+                            match written.map_err(::gludconfig::error::DaemonError::from) {
+                                Ok(()) => return decode(new),
+                                Err(::gludconfig::error::DaemonError::ValueChanged(_)) if attempts < #UPDATE_ATTEMPTS => {
+                                    #backoff(attempts)#_await;
+                                    attempts += 1;
+                                }
+                                Err(err) => return Err(err),
+                            }
+                        }
                     }
-                    pub #_async fn #reset_ident #generics(&self) -> ::zbus::Result<bool> {
+                    #deprecated
+                    pub #_async fn #reset_ident #generics(&self) -> ::gludconfig::error::DaemonResult<bool> {
                         let value = self.property_proxy.call::<_, _, bool>("reset", &(#schema_name, #name))#_await?;
                         Ok(value)
                     }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use darling::{FromDeriveInput, FromField, FromMeta};
 use proc_macro::Span;
 use proc_macro2::TokenStream;
@@ -11,6 +13,10 @@ use syn::{Field, Fields};
 struct SchemaInput {
     name: String,
     version: u32,
+    #[darling(default)]
+    translations: Option<String>,
+    #[darling(default)]
+    domain: Option<String>,
 }
 
 
@@ -45,6 +51,28 @@ struct PropertyInput {
     ty: syn::Type,
     #[darling(default)]
     value: Option<syn::Path>,
+    #[darling(default)]
+    group: Option<String>,
+    #[darling(default)]
+    order: Option<i32>,
+    #[darling(default)]
+    widget: Option<String>,
+    #[darling(default)]
+    advanced: Option<bool>,
+    #[darling(default)]
+    deprecated: Option<String>,
+    #[darling(default)]
+    replaced_by: Option<String>,
+    #[darling(default)]
+    enabled_when: Option<String>,
+    #[darling(default)]
+    computed: Option<String>,
+    #[darling(default)]
+    reject_when_disabled: Option<bool>,
+    #[darling(default)]
+    about_l10n: HashMap<String, String>,
+    #[darling(default)]
+    long_about_l10n: HashMap<String, String>,
 }
 
 pub fn expand(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -164,6 +192,33 @@ fn generate_for_property(property: PropertyInput) -> proc_macro2::TokenStream {
     let long_about = property.long_about.unwrap_or("".to_string());
     let show_in_settings = property.show_in_settings.unwrap_or(true);
     let writable = property.writable.unwrap_or(true);
+    let group = property.group.unwrap_or("".to_string());
+    let order = property.order.unwrap_or(0);
+    let advanced = property.advanced.unwrap_or(false);
+    let widget = generate_widget_hint(property.widget);
+    let deprecated = property
+        .deprecated
+        .map(|message| quote::quote!(.deprecated(#message.to_string())))
+        .unwrap_or_default();
+    let replaced_by = property
+        .replaced_by
+        .map(|key| quote::quote!(.replaced_by(#key.to_string())))
+        .unwrap_or_default();
+    let enabled_when = property
+        .enabled_when
+        .map(|condition| quote::quote!(.enabled_when(#condition.to_string())))
+        .unwrap_or_default();
+    let reject_when_disabled = property.reject_when_disabled.unwrap_or(false);
+    let computed = property
+        .computed
+        .map(|expression| quote::quote!(.computed(#expression.to_string())))
+        .unwrap_or_default();
+    let about_l10n = property.about_l10n.into_iter().map(|(locale, about)| {
+        quote::quote!(.about_translation(#locale.to_string(), #about.to_string()))
+    });
+    let long_about_l10n = property.long_about_l10n.into_iter().map(|(locale, long_about)| {
+        quote::quote!(.long_about_translation(#locale.to_string(), #long_about.to_string()))
+    });
     let sig = property.ty;
 
     let default = property.default.map(|ident |{
@@ -192,8 +247,19 @@ fn generate_for_property(property: PropertyInput) -> proc_macro2::TokenStream {
             .name(#name.to_string())
             .about(#about.to_string())
             .long_about(#long_about.to_string())
+            #(#about_l10n)*
+            #(#long_about_l10n)*
             .show_in_settings(#show_in_settings)
             .writable(#writable)
+            .group(#group.to_string())
+            .order(#order)
+            .widget(#widget)
+            .advanced(#advanced)
+            #deprecated
+            #replaced_by
+            #enabled_when
+            #computed
+            .reject_when_disabled(#reject_when_disabled)
             .signature(<#sig as ::gludconfig::zvariant::Type>::signature())
             #default
             #value
@@ -203,6 +269,20 @@ fn generate_for_property(property: PropertyInput) -> proc_macro2::TokenStream {
     stream
 }
 
+fn generate_widget_hint(widget: Option<String>) -> proc_macro2::TokenStream {
+    let variant = match widget.as_deref().unwrap_or("auto") {
+        "auto" => quote::quote!(Auto),
+        "switch" => quote::quote!(Switch),
+        "slider" => quote::quote!(Slider),
+        "file_chooser" => quote::quote!(FileChooser),
+        "color" => quote::quote!(Color),
+        other => {
+            abort!(Span::call_site(), "Unknown widget hint `{}`", other; help = "Use one of: auto, switch, slider, file_chooser, color")
+        }
+    };
+    quote::quote!(::gludconfig::property::WidgetHint::#variant)
+}
+
 fn check_field(field: &Field) -> Result<bool, proc_macro::TokenStream> {
     let mut is_field: bool = false;
     let mut is_trigger: bool = false;
@@ -232,13 +312,22 @@ fn generate_schema<'a>(
 ) -> TokenStream {
     let name = schema_input.name;
     let version = schema_input.version;
+    // The gettext domain defaults to the schema name
+    let domain = schema_input.domain.unwrap_or(name.clone());
+    let translations = schema_input
+        .translations
+        .map(|dir| quote::quote!(schema.load_translations(#dir, #domain)?;))
+        .unwrap_or_default();
     let stream = quote::quote!(
-        Ok(::gludconfig::schema::Schema::builder()
+        #[allow(unused_mut)]
+        let mut schema = ::gludconfig::schema::Schema::builder()
             .name(#name.to_string())
             .version(#version)
             .properties(::std::vec![#(#properties),*])
             .triggers(::std::vec![#(#triggers),*])
-            .build()?)
+            .build()?;
+        #translations
+        Ok(schema)
     );
     stream
 }
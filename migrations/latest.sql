@@ -1,4 +1,13 @@
 CREATE TABLE IF NOT EXISTS schemas (
     name TEXT PRIMARY KEY,
     data BLOB NOT NULL
-)
\ No newline at end of file
+);
+CREATE TABLE IF NOT EXISTS owners (
+    name TEXT PRIMARY KEY,
+    app_id TEXT,
+    executable TEXT
+);
+CREATE TABLE IF NOT EXISTS counters (
+    name TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
@@ -1,5 +1,3 @@
-use std::fmt::{Debug, Display};
-
 #[cfg(feature = "cli")]
 mod trigger {
     use zbus::dbus_proxy;
@@ -35,6 +33,16 @@ mod trigger {
             trigger_name: &str,
             value: zbus::zvariant::Value<'_>,
         ) -> zbus::Result<()>;
+
+        /// trigger_invoked_by signal
+        #[dbus_proxy(signal, name = "trigger_invoked_by")]
+        fn trigger_invoked_by(
+            &self,
+            schema_name: &str,
+            trigger_name: &str,
+            value: zbus::zvariant::Value<'_>,
+            sender: &str,
+        ) -> zbus::Result<()>;
     }
 }
 #[cfg(feature = "cli")]
@@ -62,6 +70,22 @@ mod schema {
         #[dbus_proxy(name = "register")]
         fn register(&self, data: &[u8]) -> zbus::Result<()>;
 
+        /// register_definition method
+        #[dbus_proxy(name = "register_definition")]
+        fn register_definition(&self, definition: &str, format: &str) -> zbus::Result<()>;
+
+        /// register_definition_override method
+        #[dbus_proxy(name = "register_definition_override")]
+        fn register_definition_override(&self, definition: &str, format: &str) -> zbus::Result<()>;
+
+        /// delete method
+        #[dbus_proxy(name = "delete")]
+        fn delete(&self, schema_name: &str) -> zbus::Result<()>;
+
+        /// delete_override method
+        #[dbus_proxy(name = "delete_override")]
+        fn delete_override(&self, schema_name: &str) -> zbus::Result<()>;
+
         /// reset_all method
         #[dbus_proxy(name = "reset_all")]
         fn reset_all(&self, schema_name: &str) -> zbus::Result<bool>;
@@ -85,15 +109,16 @@ mod property {
             &self,
             schema_name: &str,
             key_name: &str,
-        ) -> zbus::Result<(
-            bool,
-            String,
-            String,
-            String,
-            bool,
-            zbus::zvariant::OwnedSignature,
-            (bool, zbus::zvariant::OwnedValue),
-        )>;
+        ) -> zbus::Result<gludconfig::property::PropertyInfoV0>;
+
+        /// metadata_v2 method
+        #[dbus_proxy(name = "metadata_v2")]
+        fn metadata_v2(
+            &self,
+            schema_name: &str,
+            key_name: &str,
+            locale: &str,
+        ) -> zbus::Result<gludconfig::property::PropertyInfo>;
 
         /// reset method
         #[dbus_proxy(name = "reset")]
@@ -105,38 +130,406 @@ mod property {
             &self,
             schema_name: &str,
             key_name: &str,
-            set_value: &(bool, zbus::zvariant::Value<'_>),
+            set_value: &gludconfig::value::Nullable,
+        ) -> zbus::Result<()>;
+
+        /// set_many method
+        #[dbus_proxy(name = "set_many")]
+        fn set_many(
+            &self,
+            schema_name: &str,
+            values: &[(&str, gludconfig::value::Nullable)],
         ) -> zbus::Result<()>;
 
         /// property_changed signal
         #[dbus_proxy(signal, name = "property_changed")]
         fn property_changed(&self, schema_name: &str, key_name: &str) -> zbus::Result<()>;
+
+        /// property_changed_v2 signal
+        #[dbus_proxy(signal, name = "property_changed_v2")]
+        fn property_changed_v2(
+            &self,
+            schema_name: &str,
+            key_name: &str,
+            value: gludconfig::value::Nullable,
+            previous: gludconfig::value::Nullable,
+            serial: u64,
+            sender: &str,
+        ) -> zbus::Result<()>;
+    }
+}
+
+#[cfg(feature = "cli")]
+mod backend {
+    use anyhow::Context;
+    use gludconfig::{
+        definition::{Format, SchemaDefinition},
+        error::{DaemonError, SchemaError},
+        property::PropertyInfo,
+        schema::Schema,
+        storage::{Storage, StorageLock},
+        value::Nullable,
+    };
+    use zvariant::OwnedSignature;
+
+    use crate::{property, schema, trigger};
+
+    /// Name, version, trigger names and property names of a schema
+    pub type SchemaSummary = (String, u32, Vec<String>, Vec<String>);
+
+    /// Where commands read and write settings: through the daemon, or with `--offline` directly
+    /// in the database, running the same validation as the daemon would.
+    pub enum Backend {
+        Daemon(zbus::Connection),
+        Offline {
+            storage: Storage,
+            _lock: StorageLock,
+        },
+    }
+
+    impl Backend {
+        pub async fn connect(offline: bool) -> anyhow::Result<Self> {
+            if !offline {
+                let conn = zbus::Connection::session()
+                    .await
+                    .context("Failed to connect to the session bus")?;
+                return Ok(Backend::Daemon(conn));
+            }
+            let storage = Storage::new().await?;
+            let lock = storage.lock(false)?;
+            Ok(Backend::Offline {
+                storage,
+                _lock: lock,
+            })
+        }
+
+        /// The bus connection, for commands that need a running daemon
+        pub fn connection(&self, command: &str) -> anyhow::Result<&zbus::Connection> {
+            match self {
+                Backend::Daemon(conn) => Ok(conn),
+                Backend::Offline { .. } => {
+                    anyhow::bail!("`{}` needs the daemon and cannot be used offline", command)
+                }
+            }
+        }
+
+        fn offline(&self) -> Option<&Storage> {
+            match self {
+                Backend::Daemon(_) => None,
+                Backend::Offline { storage, .. } => Some(storage),
+            }
+        }
+
+        pub async fn schemas(&self) -> anyhow::Result<Vec<SchemaSummary>> {
+            match self {
+                Backend::Daemon(conn) => Ok(schema::SchemaProxy::new(conn).await?.all().await?),
+                Backend::Offline { storage, .. } => {
+                    Ok(storage.fetch_all().await?.iter().map(summary).collect())
+                }
+            }
+        }
+
+        pub async fn schema(&self, schema_name: &str) -> anyhow::Result<SchemaSummary> {
+            match self {
+                Backend::Daemon(conn) => Ok(schema::SchemaProxy::new(conn)
+                    .await?
+                    .metadata(schema_name)
+                    .await?),
+                Backend::Offline { storage, .. } => {
+                    Ok(summary(&storage.get_schema(schema_name.to_string()).await?))
+                }
+            }
+        }
+
+        pub async fn property(
+            &self,
+            schema_name: &str,
+            property_name: &str,
+            locale: Option<&str>,
+        ) -> anyhow::Result<PropertyInfo> {
+            match self {
+                Backend::Daemon(conn) => Ok(property::PropertyProxy::new(conn)
+                    .await?
+                    .metadata_v2(schema_name, property_name, locale.unwrap_or_default())
+                    .await?),
+                Backend::Offline { storage, .. } => storage
+                    .get_schema(schema_name.to_string())
+                    .await?
+                    .property_info(property_name, locale),
+            }
+        }
+
+        pub async fn set(
+            &self,
+            schema_name: &str,
+            property_name: &str,
+            value: Nullable,
+        ) -> anyhow::Result<()> {
+            self.set_many(schema_name, vec![(property_name, value)])
+                .await
+        }
+
+        /// Writes every value or, if one of them fails, none
+        pub async fn set_many(
+            &self,
+            schema_name: &str,
+            values: Vec<(&str, Nullable)>,
+        ) -> anyhow::Result<()> {
+            let Some(storage) = self.offline() else {
+                let proxy = property::PropertyProxy::new(self.connection("set")?).await?;
+                match values.as_slice() {
+                    [(property_name, value)] => {
+                        proxy.set(schema_name, property_name, value).await?
+                    }
+                    values => proxy.set_many(schema_name, values).await?,
+                }
+                return Ok(());
+            };
+            let mut schema = storage.get_schema(schema_name.to_string()).await?;
+            for (property_name, value) in values {
+                schema.set(property_name, value)?;
+            }
+            schema.recompute();
+            storage.update_schema(&schema).await?;
+            warn_no_signals();
+            Ok(())
+        }
+
+        pub async fn reset(&self, schema_name: &str, property_name: &str) -> anyhow::Result<bool> {
+            let Some(storage) = self.offline() else {
+                let proxy = property::PropertyProxy::new(self.connection("reset")?).await?;
+                return Ok(proxy.reset(schema_name, property_name).await?);
+            };
+            let mut schema = storage.get_schema(schema_name.to_string()).await?;
+            let was_reset = schema
+                .resolve_mut(property_name)
+                .ok_or(SchemaError::PropertyNotFound(
+                    schema_name.to_string(),
+                    property_name.to_string(),
+                ))?
+                .reset();
+            schema.recompute();
+            storage.update_schema(&schema).await?;
+            warn_no_signals();
+            Ok(was_reset)
+        }
+
+        pub async fn reset_all(&self, schema_name: &str) -> anyhow::Result<bool> {
+            let Some(storage) = self.offline() else {
+                let proxy = schema::SchemaProxy::new(self.connection("reset")?).await?;
+                return Ok(proxy.reset_all(schema_name).await?);
+            };
+            let mut schema = storage.get_schema(schema_name.to_string()).await?;
+            let (res, _) = schema.reset_all();
+            storage.update_schema(&schema).await?;
+            warn_no_signals();
+            Ok(res)
+        }
+
+        /// Name and signature of a trigger
+        pub async fn trigger(
+            &self,
+            schema_name: &str,
+            trigger_name: &str,
+        ) -> anyhow::Result<(String, OwnedSignature)> {
+            match self {
+                Backend::Daemon(conn) => Ok(trigger::TriggerProxy::new(conn)
+                    .await?
+                    .metadata(schema_name, trigger_name)
+                    .await?),
+                Backend::Offline { storage, .. } => storage
+                    .get_schema(schema_name.to_string())
+                    .await?
+                    .into_triggers()
+                    .find(|trigger| trigger.name() == trigger_name)
+                    .map(|trigger| (trigger.name().to_string(), trigger.signature().into()))
+                    .ok_or(
+                        SchemaError::TriggerNotFound(
+                            schema_name.to_string(),
+                            trigger_name.to_string(),
+                        )
+                        .into(),
+                    ),
+            }
+        }
+
+        /// Registers a schema, or replaces the definition of an existing one keeping its values
+        pub async fn register_definition(
+            &self,
+            definition: &str,
+            format: Format,
+            override_owner: bool,
+        ) -> anyhow::Result<()> {
+            let Some(storage) = self.offline() else {
+                let proxy = schema::SchemaProxy::new(self.connection("register")?).await?;
+                if override_owner {
+                    proxy
+                        .register_definition_override(definition, format.as_str())
+                        .await?;
+                } else {
+                    proxy
+                        .register_definition(definition, format.as_str())
+                        .await?;
+                }
+                return Ok(());
+            };
+            let mut schema = SchemaDefinition::parse(definition, format)?.into_schema()?;
+            match storage.get_schema(schema.name().to_string()).await {
+                Ok(old) => {
+                    schema.keep_values(&old);
+                    storage.update_schema(&schema).await?;
+                }
+                Err(DaemonError::SchemaNotFound(_)) => storage.new_schema(&schema).await?,
+                Err(err) => return Err(err.into()),
+            }
+            warn_no_signals();
+            Ok(())
+        }
+
+        /// Offline there is no caller to check ownership against, so `override_owner` only
+        /// matters with the daemon
+        pub async fn delete(&self, schema_name: &str, override_owner: bool) -> anyhow::Result<()> {
+            let Some(storage) = self.offline() else {
+                let proxy = schema::SchemaProxy::new(self.connection("delete")?).await?;
+                if override_owner {
+                    proxy.delete_override(schema_name).await?;
+                } else {
+                    proxy.delete(schema_name).await?;
+                }
+                return Ok(());
+            };
+            // Fails with a proper error if the schema does not exist.
+            storage.get_schema(schema_name.to_string()).await?;
+            storage.delete_schema(schema_name.to_string()).await?;
+            warn_no_signals();
+            Ok(())
+        }
+    }
+
+    /// Offline writes are not announced, running clients keep their old values until restarted
+    fn warn_no_signals() {
+        eprintln!("warning: running offline, no change signals were sent");
+    }
+
+    fn summary(schema: &Schema) -> SchemaSummary {
+        (
+            schema.name().to_string(),
+            schema.version(),
+            schema
+                .triggers()
+                .map(|trigger| trigger.name().to_string())
+                .collect(),
+            schema
+                .properties()
+                .map(|property| property.name().to_string())
+                .collect(),
+        )
     }
 }
 
 #[cfg(feature = "cli")]
 mod cli {
+    use anyhow::Context;
     use clap::{Parser, Subcommand};
     use futures_util::StreamExt;
-    use zvariant::OwnedValue;
+    use zbus::DBusError;
 
-    use crate::{property, schema, trigger};
+    use crate::{backend::Backend, property, trigger};
 
     #[derive(Parser)]
     #[command(
         author = "gludconfig",
         version,
         name = "gludconfig",
-        about = "CLI Tool to interact with the gludconfig dbus daemon"
+        about = "CLI Tool to interact with the gludconfig dbus daemon",
+        after_long_help = EXIT_CODES
     )]
-    pub enum GludCli {
+    pub struct GludCli {
+        /// Output format, `text` if left out, except for `watch` which prints `ndjson`. Under `text`
+        /// and `table` property values are printed in the GVariant text format
+        #[arg(short, long, global = true, value_enum)]
+        pub format: Option<OutputFormat>,
+        /// Work directly on the database instead of going through the daemon, e.g. when there is
+        /// no session bus. Fails while the daemon is running, and sends no change signals
+        #[arg(long, global = true)]
+        pub offline: bool,
+        #[command(subcommand)]
+        pub command: Command,
+    }
+
+    const EXIT_CODES: &str = "Exit codes:
+  0  success
+  1  any other failure
+  2  invalid input, such as a value that does not match the signature
+  3  schema, property or trigger not found
+  4  permission denied, such as writing to a read-only property
+  5  the gludconfig daemon is unreachable";
+
+    #[derive(Subcommand)]
+    pub enum Command {
         #[command(subcommand)]
         Property(PropertyCommand),
         #[command(subcommand)]
         Schema(SchemaCommand),
-        #[command(subcommand)]
+        #[command(subcommand, name = "trigger", alias = "trigger-command")]
         TriggerCommand(TriggerCommand),
 
+        #[command(
+            author = "gludconfig",
+            name = "watch",
+            version,
+            about = "Print every property change across all schemas",
+            long_about = "Print every property change across all schemas, with the time, the old and new value and the client that made the change! Prints one Json document per event, use `--format text` for one line of text instead"
+        )]
+        Watch {
+            /// Only watch schemas whose name starts with this prefix
+            #[arg(long = "schema")]
+            schema_prefix: Option<String>,
+            /// Also print invoked triggers
+            #[arg(long)]
+            include_triggers: bool,
+        },
+        #[command(
+            author = "gludconfig",
+            name = "dump",
+            version,
+            about = "Print every value that differs from its default as a keyfile",
+            long_about = "Print every value that differs from its default as a keyfile, grouped by schema. Values are in the GVariant text format. The output can be applied again with `load`"
+        )]
+        Dump {
+            /// Only dump schemas whose name starts with this prefix
+            schema_prefix: Option<String>,
+        },
+        #[command(
+            author = "gludconfig",
+            name = "load",
+            version,
+            about = "Apply a keyfile written by `dump`",
+            long_about = "Apply a keyfile written by `dump`! The whole file is validated first, then every schema is written in one go, so a schema is either fully updated or not at all"
+        )]
+        Load {
+            /// Keyfile to read, defaults to stdin
+            file: Option<std::path::PathBuf>,
+        },
+
+        #[command(
+            author = "gludconfig",
+            name = "diff",
+            version,
+            about = "List every property whose value differs from its default",
+            long_about = "List every property whose value differs from its default! With `--backup` or `--database` values are compared against a keyfile written by `dump` or another database instead. Values are shown in the GVariant text format, computed properties are left out"
+        )]
+        Diff {
+            /// Only diff schemas whose name starts with this prefix
+            schema_prefix: Option<String>,
+            /// Compare against a keyfile written by `dump`
+            #[arg(long, conflicts_with = "database")]
+            backup: Option<std::path::PathBuf>,
+            /// Compare against another database, e.g. a copy of someone else's `data.db`
+            #[arg(long)]
+            database: Option<std::path::PathBuf>,
+        },
+
         #[command(author = "gludconfig", name = "gen", version, about = "Tool to generate interfacing code with gludconfig schemas", long_about = None)]
         GenCode {
             #[arg(short, long)]
@@ -144,6 +537,30 @@ mod cli {
             schema: String,
             name: String,
         },
+
+        #[command(
+            author = "gludconfig",
+            name = "completions",
+            version,
+            about = "Print a shell completion script",
+            long_about = "Print a shell completion script! For bash, zsh and fish the script asks gludconfig for schema, property and trigger names, and the choices of a property, while completing. E.g. `gludconfig completions bash > ~/.local/share/bash-completion/completions/gludconfig`"
+        )]
+        Completions { shell: clap_complete::Shell },
+        #[cfg(feature = "tui")]
+        #[command(
+            author = "gludconfig",
+            name = "tui",
+            version,
+            about = "Browse and edit settings in a full screen editor",
+            long_about = "Browse and edit settings in a full screen editor! Lists schemas and their properties, shows a property's description, current value, default and choices, and updates live when another client changes a value. Needs the daemon"
+        )]
+        Tui,
+        /// Prints the candidates for the last of `words`, used by the completion scripts
+        #[command(name = "__complete", hide = true)]
+        Complete {
+            #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+            words: Vec<String>,
+        },
     }
 
     #[derive(Subcommand)]
@@ -174,6 +591,18 @@ mod cli {
             schema_name: String,
             trigger_name: String,
         },
+        #[command(
+            name = "invoke",
+            author = "gludconfig",
+            version,
+            about = "Invoke a trigger with a payload",
+            long_about = "Invoke a trigger with a payload! The payload is parsed against the trigger's signature, in the GVariant text format (e.g. `(1, 2)`) or as Json with `--format json`"
+        )]
+        Invoke {
+            schema_name: String,
+            trigger_name: String,
+            payload: String,
+        },
     }
 
     #[derive(Subcommand)]
@@ -208,6 +637,34 @@ mod cli {
             long_about = "Reset all values in a schema recursively! Returns false even if one of the keys is not writable!"
         )]
         ResetRecursively { schema_name: String },
+        #[command(
+            author = "gludconfig",
+            name = "register",
+            version,
+            about = "Register a schema from a TOML or Json definition file",
+            long_about = "Register a schema from a TOML or Json definition file! The format is picked by the file extension, see `gludconfig::definition::SchemaDefinition` for the layout"
+        )]
+        Register {
+            file: std::path::PathBuf,
+            /// Replace the schema even if another application registered it, needs admin access
+            #[arg(long)]
+            override_owner: bool,
+        },
+        #[command(
+            author = "gludconfig",
+            name = "delete",
+            version,
+            about = "Delete a schema along with all of its values"
+        )]
+        Delete {
+            schema_name: String,
+            /// Do not ask for confirmation
+            #[arg(short, long)]
+            yes: bool,
+            /// Delete the schema even if another application registered it, needs admin access
+            #[arg(long)]
+            override_owner: bool,
+        },
     }
 
     #[derive(Subcommand)]
@@ -222,11 +679,36 @@ mod cli {
             author = "gludconfig",
             version,
             name = "metadata",
-            about = "Get metadata for a property(including its current value). In Json Format"
+            about = "Get metadata for a property(including its current value)"
         )]
         Metadata {
             schema_name: String,
             property_name: String,
+            /// Locale to translate the descriptions to, defaults to the one from the environment (LC_ALL, LC_MESSAGES, LANG)
+            #[arg(short, long)]
+            locale: Option<String>,
+        },
+        #[command(
+            author = "gludconfig",
+            name = "get",
+            version,
+            about = "Print the current value of a property"
+        )]
+        Get {
+            schema_name: String,
+            property_name: String,
+        },
+        #[command(
+            author = "gludconfig",
+            name = "set",
+            version,
+            about = "Set a property to a new value",
+            long_about = "Set a property to a new value! The value is parsed against the property's signature, in the GVariant text format (e.g. `['a', 'b']`, `(1, true)`, `nothing`) or as Json with `--format json`"
+        )]
+        Set {
+            schema_name: String,
+            property_name: String,
+            value: String,
         },
         #[command(
             author = "gludconfig",
@@ -252,9 +734,279 @@ mod cli {
         },
     }
 
-    use gludconfig::schema::Schema;
+    #[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum OutputFormat {
+        /// Human readable, values in the GVariant text format
+        #[default]
+        Text,
+        /// Pretty printed Json
+        Json,
+        /// Aligned columns, one row per entry
+        Table,
+        /// One compact Json document per line
+        Ndjson,
+    }
+
+    impl OutputFormat {
+        /// Values given on the command line are read as Json under `json` and `ndjson`
+        fn reads_json(self) -> bool {
+            matches!(self, OutputFormat::Json | OutputFormat::Ndjson)
+        }
+    }
+
+    /// What a command prints on success
+    pub enum Output {
+        None,
+        /// Structured data, rendered according to the `OutputFormat`
+        Data(serde_json::Value),
+        /// A short confirmation, wrapped in `{"message": ..}` under `json` and `ndjson`
+        Message(String),
+        /// Printed as is under every format, e.g. a keyfile or generated code
+        Raw(String),
+    }
+
+    impl Output {
+        pub fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+            Ok(match (self, format) {
+                (Output::None, _) => String::new(),
+                (Output::Raw(text), _) => text.clone(),
+                (Output::Message(message), OutputFormat::Text | OutputFormat::Table) => {
+                    message.clone()
+                }
+                (Output::Message(message), format) => {
+                    Output::Data(serde_json::json!({ "message": message })).render(format)?
+                }
+                (Output::Data(data), OutputFormat::Json) => serde_json::to_string_pretty(data)?,
+                (Output::Data(serde_json::Value::Array(items)), OutputFormat::Ndjson) => items
+                    .iter()
+                    .map(serde_json::to_string)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("\n"),
+                (Output::Data(data), OutputFormat::Ndjson) => serde_json::to_string(data)?,
+                (Output::Data(data), OutputFormat::Text) => render_text(data),
+                (Output::Data(data), OutputFormat::Table) => render_table(data),
+            })
+        }
+    }
+
+    /// A scalar as is, a list of scalars comma separated and anything else as compact Json
+    fn render_cell(value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::String(string) => string.clone(),
+            serde_json::Value::Array(items) if items.iter().all(|item| !is_nested(item)) => {
+                items.iter().map(render_cell).collect::<Vec<_>>().join(", ")
+            }
+            other => other.to_string(),
+        }
+    }
+
+    fn is_nested(value: &serde_json::Value) -> bool {
+        matches!(
+            value,
+            serde_json::Value::Array(_) | serde_json::Value::Object(_)
+        )
+    }
+
+    /// Objects become aligned `key: value` lines, lists one entry per line
+    fn render_text(data: &serde_json::Value) -> String {
+        match data {
+            serde_json::Value::Object(object) => {
+                let width = object.keys().map(|key| key.len() + 1).max().unwrap_or(0);
+                object
+                    .iter()
+                    .map(|(key, value)| {
+                        format!("{:width$} {}", format!("{}:", key), render_cell(value))
+                            .trim_end()
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            serde_json::Value::Array(items) => {
+                let separator = match items.iter().any(is_nested) {
+                    true => "\n\n",
+                    false => "\n",
+                };
+                items
+                    .iter()
+                    .map(render_text)
+                    .collect::<Vec<_>>()
+                    .join(separator)
+            }
+            other => render_cell(other),
+        }
+    }
+
+    /// Lists of objects become one row per object, a single object one row per key
+    fn render_table(data: &serde_json::Value) -> String {
+        let (header, rows): (Vec<String>, Vec<Vec<String>>) = match data {
+            serde_json::Value::Array(items) if items.iter().all(|item| item.is_object()) => {
+                let header: Vec<String> = items
+                    .first()
+                    .and_then(serde_json::Value::as_object)
+                    .map(|object| object.keys().cloned().collect())
+                    .unwrap_or_default();
+                let rows = items
+                    .iter()
+                    .map(|item| {
+                        header
+                            .iter()
+                            .map(|key| item.get(key).map(render_cell).unwrap_or_default())
+                            .collect()
+                    })
+                    .collect();
+                (header, rows)
+            }
+            serde_json::Value::Object(object) => (
+                vec!["key".to_string(), "value".to_string()],
+                object
+                    .iter()
+                    .map(|(key, value)| vec![key.clone(), render_cell(value)])
+                    .collect(),
+            ),
+            other => return render_text(other),
+        };
+
+        let header: Vec<String> = header.iter().map(|key| key.to_uppercase()).collect();
+        let mut widths = vec![0; header.len()];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        std::iter::once(&header)
+            .chain(&rows)
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = *width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The process exit codes, see `EXIT_CODES`
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum ExitCode {
+        Failure = 1,
+        Invalid = 2,
+        NotFound = 3,
+        PermissionDenied = 4,
+        Unreachable = 5,
+    }
+
+    impl ExitCode {
+        /// Classifies an error by the first cause in its chain that is recognized
+        pub fn of(err: &anyhow::Error) -> Self {
+            err.chain()
+                .find_map(|cause| {
+                    if let Some(err) = cause.downcast_ref::<zbus::Error>() {
+                        return Self::of_zbus(err);
+                    }
+                    if let Some(err) = cause.downcast_ref::<DaemonError>() {
+                        return Self::of_daemon(err);
+                    }
+                    if let Some(err) = cause.downcast_ref::<zbus::fdo::Error>() {
+                        return Self::of_dbus(err.name().as_str());
+                    }
+                    if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+                        return match err.kind() {
+                            std::io::ErrorKind::NotFound => Some(ExitCode::NotFound),
+                            std::io::ErrorKind::PermissionDenied => {
+                                Some(ExitCode::PermissionDenied)
+                            }
+                            _ => None,
+                        };
+                    }
+                    if let Some(
+                        SchemaError::PropertyNotFound(..) | SchemaError::TriggerNotFound(..),
+                    ) = cause.downcast_ref::<SchemaError>()
+                    {
+                        return Some(ExitCode::NotFound);
+                    }
+                    if let Some(PropertyError::NotWritable) = cause.downcast_ref::<PropertyError>()
+                    {
+                        return Some(ExitCode::PermissionDenied);
+                    }
+                    let invalid = cause.is::<ValueError>()
+                        || cause.is::<PropertyError>()
+                        || cause.is::<SchemaError>()
+                        || cause.is::<DefinitionError>()
+                        || cause.is::<KeyFileError>()
+                        || cause.is::<ExprError>()
+                        || cause.is::<BuilderError>()
+                        || cause.is::<serde_json::Error>();
+                    invalid.then_some(ExitCode::Invalid)
+                })
+                .unwrap_or(ExitCode::Failure)
+        }
+
+        fn of_zbus(err: &zbus::Error) -> Option<Self> {
+            match err {
+                zbus::Error::Address(_)
+                | zbus::Error::InputOutput(_)
+                | zbus::Error::Handshake(_) => Some(ExitCode::Unreachable),
+                zbus::Error::MethodError(..) => match DaemonError::from(err.clone()) {
+                    DaemonError::ZBus(zbus::Error::MethodError(name, ..)) => {
+                        Self::of_dbus(name.as_str())
+                    }
+                    err => Self::of_daemon(&err),
+                },
+                zbus::Error::FDO(err) => Self::of_dbus(err.name().as_str()),
+                _ => None,
+            }
+        }
+
+        fn of_daemon(err: &DaemonError) -> Option<Self> {
+            match err {
+                DaemonError::ZBus(err) => Self::of_zbus(err),
+                DaemonError::SchemaNotFound(_)
+                | DaemonError::PropertyNotFound(_)
+                | DaemonError::TriggerNotFound(_) => Some(ExitCode::NotFound),
+                DaemonError::NotWritable(_) | DaemonError::PermissionDenied(_) => {
+                    Some(ExitCode::PermissionDenied)
+                }
+                DaemonError::NotInChoices(_)
+                | DaemonError::Disabled(_)
+                | DaemonError::SignatureMismatch(_)
+                | DaemonError::InvalidValue(_)
+                | DaemonError::InvalidSchema(_)
+                | DaemonError::ValueChanged(_) => Some(ExitCode::Invalid),
+                DaemonError::Storage(_) | DaemonError::Failed(_) => None,
+            }
+        }
+
+        fn of_dbus(name: &str) -> Option<Self> {
+            match name.strip_prefix("org.freedesktop.DBus.Error.")? {
+                "ServiceUnknown" | "NameHasNoOwner" | "NoReply" | "Disconnected" => {
+                    Some(ExitCode::Unreachable)
+                }
+                "AccessDenied" | "AuthFailed" => Some(ExitCode::PermissionDenied),
+                "InvalidArgs" | "InvalidSignature" => Some(ExitCode::Invalid),
+                _ => None,
+            }
+        }
+    }
+
+    use gludconfig::{
+        definition::{Format, SchemaDefinition},
+        error::{
+            BuilderError, DaemonError, DefinitionError, ExprError, KeyFileError, PropertyError,
+            SchemaError, ValueError,
+        },
+        keyfile::{Entry, Group, KeyFile},
+        property::PropertyInfo,
+        schema::Schema,
+        storage::Storage,
+        value::{Nullable, Value},
+    };
+    use std::collections::BTreeMap;
     use zvariant::Basic;
-    use zvariant::Signature;
+    use zvariant::{OwnedValue, Signature};
 
     fn to_rust_type(ty: &Signature<'static>, input: bool, as_ref: bool) -> String {
         fn iter_to_rust_type(
@@ -371,7 +1123,15 @@ mod cli {
         writeln!(write, "trait {} {{", name)?;
 
         for property in schema.properties() {
-            writeln!(write, "    #[property(name = \"{}\")]", property.name())?;
+            match property.is_deprecated() {
+                true => writeln!(
+                    write,
+                    "    #[property(name = \"{}\", deprecated = {:?})]",
+                    property.name(),
+                    property.deprecation()
+                )?,
+                false => writeln!(write, "    #[property(name = \"{}\")]", property.name())?,
+            }
             let _async = blocking.then(|| "").unwrap_or("async");
             writeln!(
                 write,
@@ -397,77 +1157,565 @@ mod cli {
         Ok(())
     }
 
-    pub async fn list_schemas(conn: &zbus::Connection) -> anyhow::Result<String> {
-        let proxy = schema::SchemaProxy::new(&conn).await?;
-        let schemas = proxy.all().await?;
-        let json = serde_json::json!({
-            "schemas": schemas,
-        });
-        return Ok(serde_json::to_string_pretty(&json)?);
+    const BASH_COMPLETION: &str = r#"_gludconfig() {
+    local IFS=$'\n'
+    COMPREPLY=($(gludconfig __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null))
+}
+complete -o default -F _gludconfig gludconfig"#;
+
+    const ZSH_COMPLETION: &str = r#"#compdef gludconfig
+_gludconfig() {
+    local -a candidates
+    candidates=("${(@f)$(gludconfig __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    if (( ${#candidates[@]} )) && [[ -n "${candidates[1]}" ]]; then
+        compadd -a candidates
+    else
+        _files
+    fi
+}
+compdef _gludconfig gludconfig"#;
+
+    const FISH_COMPLETION: &str = r#"function __gludconfig_complete
+    set -l candidates (gludconfig __complete -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null)
+    if test (count $candidates) -gt 0
+        printf '%s\n' $candidates
+    else
+        __fish_complete_path (commandline -ct)
+    end
+end
+complete -c gludconfig -f -a '(__gludconfig_complete)'"#;
+
+    /// The bash, zsh and fish scripts call `gludconfig __complete`, other shells get the static
+    /// completions of `clap_complete` without schema, property and trigger names
+    pub fn completion_script(shell: clap_complete::Shell) -> anyhow::Result<Output> {
+        use clap::CommandFactory;
+
+        Ok(Output::Raw(match shell {
+            clap_complete::Shell::Bash => BASH_COMPLETION.to_string(),
+            clap_complete::Shell::Zsh => ZSH_COMPLETION.to_string(),
+            clap_complete::Shell::Fish => FISH_COMPLETION.to_string(),
+            shell => {
+                let mut script = vec![];
+                clap_complete::generate(shell, &mut GludCli::command(), "gludconfig", &mut script);
+                String::from_utf8(script)?
+            }
+        }))
+    }
+
+    /// Completes the last of `words`, the command line without the program name. Subcommands and
+    /// options come from the clap definition, names and choices from the daemon, or the database
+    /// with `--offline`. Failures complete nothing.
+    pub async fn complete(words: Vec<String>) -> Output {
+        use clap::CommandFactory;
+
+        let (current, previous) = words
+            .split_last()
+            .map(|(current, previous)| (current.as_str(), previous))
+            .unwrap_or_default();
+
+        let mut root = GludCli::command();
+        root.build();
+        let mut cmd = &root;
+        let mut positionals = vec![];
+        let mut pending: Option<&clap::Arg> = None;
+        for word in previous {
+            if pending.take().is_some() {
+                continue;
+            }
+            if let Some(arg) = find_option(cmd, word) {
+                let inline = word.contains('=') || (!word.starts_with("--") && word.len() > 2);
+                let takes_value = arg.get_num_args().is_some_and(|n| n.takes_values());
+                if takes_value && !inline {
+                    pending = Some(arg);
+                }
+            } else if word.starts_with('-') {
+                continue;
+            } else if let Some(subcommand) = cmd.find_subcommand(word) {
+                cmd = subcommand;
+            } else {
+                positionals.push(word.as_str());
+            }
+        }
+
+        let candidates = if let Some(arg) = pending {
+            arg.get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_string())
+                .collect()
+        } else if current.starts_with('-') {
+            cmd.get_arguments()
+                .filter(|arg| !arg.is_hide_set())
+                .filter_map(|arg| Some(format!("--{}", arg.get_long()?)))
+                .collect()
+        } else if cmd.has_subcommands() {
+            cmd.get_subcommands()
+                .filter(|subcommand| !subcommand.is_hide_set())
+                .map(|subcommand| subcommand.get_name().to_string())
+                .collect()
+        } else if let Some(arg) = cmd.get_positionals().nth(positionals.len()) {
+            let offline = previous.iter().any(|word| word == "--offline");
+            complete_value(arg.get_id().as_str(), &positionals, offline)
+                .await
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+
+        Output::Raw(
+            candidates
+                .into_iter()
+                .filter(|candidate| candidate.starts_with(current))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+
+    fn find_option<'c>(cmd: &'c clap::Command, word: &str) -> Option<&'c clap::Arg> {
+        if let Some(long) = word.strip_prefix("--") {
+            let long = long.split('=').next()?;
+            return cmd.get_arguments().find(|arg| arg.get_long() == Some(long));
+        }
+        let short = word.strip_prefix('-')?.chars().next()?;
+        cmd.get_arguments()
+            .find(|arg| arg.get_short() == Some(short))
+    }
+
+    /// Candidates for the positional argument `id`, `positionals` being the ones before it
+    async fn complete_value(
+        id: &str,
+        positionals: &[&str],
+        offline: bool,
+    ) -> anyhow::Result<Vec<String>> {
+        let backend = Backend::connect(offline).await?;
+        let schema_name = positionals.first().copied().unwrap_or_default();
+        Ok(match id {
+            "schema_name" | "schema" | "schema_prefix" => backend
+                .schemas()
+                .await?
+                .into_iter()
+                .map(|(name, _, _, _)| name)
+                .collect(),
+            "property_name" => backend.schema(schema_name).await?.3,
+            "trigger_name" => backend.schema(schema_name).await?.2,
+            "value" => {
+                let property_name = positionals.get(1).copied().unwrap_or_default();
+                backend
+                    .property(schema_name, property_name, None)
+                    .await?
+                    .choices
+                    .iter()
+                    .map(Nullable::to_string)
+                    .collect()
+            }
+            _ => vec![],
+        })
+    }
+
+    pub async fn list_schemas(backend: &Backend) -> anyhow::Result<Output> {
+        let schemas = backend.schemas().await?;
+        let json = schemas
+            .into_iter()
+            .map(|(name, version, triggers, properties)| {
+                serde_json::json!({
+                    "name": name,
+                    "version": version,
+                    "triggers": triggers,
+                    "properties": properties,
+                })
+            })
+            .collect();
+        Ok(Output::Data(json))
+    }
+
+    pub async fn register_schema(
+        file: std::path::PathBuf,
+        override_owner: bool,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let format = Format::from_path(&file)
+            .ok_or(DefinitionError::UnknownFormat(file.display().to_string()))?;
+        let definition = std::fs::read_to_string(&file)
+            .with_context(|| format!("While reading {}", file.display()))?;
+        // Validated locally first, so mistakes in the file are reported as invalid input.
+        let name = SchemaDefinition::parse(&definition, format)
+            .and_then(SchemaDefinition::into_schema)
+            .map_err(|err| err.context(format!("While loading {}", file.display())))?
+            .name()
+            .to_string();
+
+        backend
+            .register_definition(&definition, format, override_owner)
+            .await?;
+        Ok(Output::Message(format!("Registered schema {}", name)))
+    }
+
+    pub async fn delete_schema(
+        schema_name: String,
+        yes: bool,
+        override_owner: bool,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        use std::io::Write;
+
+        if !yes {
+            eprint!(
+                "Delete schema {} along with all of its values? [y/N] ",
+                schema_name
+            );
+            std::io::stderr().flush()?;
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                anyhow::bail!("Aborted, schema {} was not deleted", schema_name);
+            }
+        }
+
+        backend.delete(&schema_name, override_owner).await?;
+        Ok(Output::Message(format!("Deleted schema {}", schema_name)))
+    }
+
+    pub async fn dump(schema_prefix: Option<String>, backend: &Backend) -> anyhow::Result<Output> {
+        let mut keyfile = KeyFile::default();
+
+        let mut schemas = backend.schemas().await?;
+        schemas.sort_by(|a, b| a.0.cmp(&b.0));
+        for (schema_name, _, _, properties) in schemas {
+            if !schema_name.starts_with(schema_prefix.as_deref().unwrap_or_default()) {
+                continue;
+            }
+            let mut entries = vec![];
+            for property_name in properties {
+                let info = backend.property(&schema_name, &property_name, None).await?;
+                // Only values that `load` can write back are dumped.
+                let loadable =
+                    info.writable && info.computed.is_empty() && info.replaced_by.is_empty();
+                if loadable && info.current != info.default {
+                    entries.push(Entry {
+                        key: property_name,
+                        value: info.current.to_string(),
+                        line: 0,
+                    });
+                }
+            }
+            if !entries.is_empty() {
+                keyfile.groups.push(Group {
+                    name: schema_name,
+                    line: 0,
+                    entries,
+                });
+            }
+        }
+        Ok(Output::Raw(keyfile.to_string().trim_end().to_string()))
+    }
+
+    pub async fn load(
+        file: Option<std::path::PathBuf>,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        use std::io::Read;
+
+        let text = match &file {
+            Some(file) => std::fs::read_to_string(file)
+                .with_context(|| format!("While reading {}", file.display()))?,
+            None => {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            }
+        };
+        let keyfile = KeyFile::parse(&text)?;
+
+        let mut batches = vec![];
+        for group in &keyfile.groups {
+            let (_, _, _, properties) = backend
+                .schema(&group.name)
+                .await
+                .map_err(|_| KeyFileError::UnknownSchema(group.line, group.name.clone()))?;
+            let mut values = vec![];
+            for entry in &group.entries {
+                if !properties.contains(&entry.key) {
+                    return Err(KeyFileError::UnknownKey(
+                        entry.line,
+                        entry.key.clone(),
+                        group.name.clone(),
+                    )
+                    .into());
+                }
+                let info = backend.property(&group.name, &entry.key, None).await?;
+                if !info.writable {
+                    return Err(KeyFileError::NotWritable(entry.line, entry.key.clone()).into());
+                }
+                let value = Value::parse(&entry.value, &info.signature).map_err(|err| {
+                    KeyFileError::InvalidValue(entry.line, entry.key.clone(), format!("{:#}", err))
+                })?;
+                values.push((entry.key.as_str(), value.into()));
+            }
+            batches.push((group.name.as_str(), values));
+        }
+
+        for (schema_name, values) in batches {
+            backend
+                .set_many(schema_name, values)
+                .await
+                .map_err(|err| err.context(format!("While loading {}", schema_name)))?;
+        }
+        Ok(Output::None)
+    }
+
+    /// Values of a side of a diff, in the GVariant text format
+    type Snapshot = BTreeMap<(String, String), String>;
+
+    pub async fn diff(
+        schema_prefix: Option<String>,
+        backup: Option<std::path::PathBuf>,
+        database: Option<std::path::PathBuf>,
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let prefix = schema_prefix.unwrap_or_default();
+
+        let mut signatures = BTreeMap::new();
+        let mut current = Snapshot::new();
+        let mut defaults = Snapshot::new();
+        for (schema_name, _, _, properties) in backend.schemas().await? {
+            if !schema_name.starts_with(&prefix) {
+                continue;
+            }
+            for property_name in properties {
+                let info = backend.property(&schema_name, &property_name, None).await?;
+                // Like `dump`, deprecated aliases are left out, they show their replacement.
+                if !info.computed.is_empty() || !info.replaced_by.is_empty() {
+                    continue;
+                }
+                let key = (schema_name.clone(), property_name);
+                current.insert(key.clone(), info.current.to_string());
+                defaults.insert(key.clone(), info.default.to_string());
+                signatures.insert(key, info.signature);
+            }
+        }
+
+        let (baseline, label) = match (backup, database) {
+            (Some(file), _) => {
+                let text = std::fs::read_to_string(&file)
+                    .with_context(|| format!("While reading {}", file.display()))?;
+                // A dump only holds values that differ from the default.
+                let mut baseline = defaults;
+                for group in KeyFile::parse(&text)?.groups {
+                    if !group.name.starts_with(&prefix) {
+                        continue;
+                    }
+                    for entry in group.entries {
+                        let key = (group.name.clone(), entry.key);
+                        let value = match signatures.get(&key) {
+                            Some(signature) => {
+                                let value: Nullable = Value::parse(&entry.value, signature)
+                                    .map_err(|err| {
+                                        KeyFileError::InvalidValue(
+                                            entry.line,
+                                            key.1.clone(),
+                                            format!("{:#}", err),
+                                        )
+                                    })?
+                                    .into();
+                                value.to_string()
+                            }
+                            None => entry.value,
+                        };
+                        baseline.insert(key, value);
+                    }
+                }
+                (baseline, file.display().to_string())
+            }
+            (None, Some(path)) => {
+                let storage = Storage::open(path.to_string_lossy()).await?;
+                let mut baseline = Snapshot::new();
+                for schema in storage.fetch_all().await? {
+                    if !schema.name().starts_with(&prefix) {
+                        continue;
+                    }
+                    let values = schema.values();
+                    for property in schema.properties() {
+                        if property.computed().is_some() || property.replaced_by().is_some() {
+                            continue;
+                        }
+                        let Some(value) = values.get(property.name()) else {
+                            continue;
+                        };
+                        let key = (schema.name().to_string(), property.name().to_string());
+                        baseline.insert(key, value.to_string());
+                    }
+                }
+                (baseline, path.display().to_string())
+            }
+            (None, None) => (defaults, "the defaults".to_string()),
+        };
+
+        let mut keys = baseline.keys().chain(current.keys()).collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        let changes = keys
+            .into_iter()
+            .map(|key| (key, baseline.get(key), current.get(key)))
+            .filter(|(_, old, new)| old != new)
+            .collect::<Vec<_>>();
+
+        if format != OutputFormat::Text {
+            return Ok(Output::Data(
+                changes
+                    .into_iter()
+                    .map(|((schema_name, property_name), old, new)| {
+                        serde_json::json!({
+                            "schema": schema_name,
+                            "property": property_name,
+                            "old": old,
+                            "new": new,
+                        })
+                    })
+                    .collect(),
+            ));
+        }
+        if changes.is_empty() {
+            return Ok(Output::Message(format!("No differences to {}", label)));
+        }
+        let missing = "(missing)".to_string();
+        Ok(Output::Message(
+            changes
+                .into_iter()
+                .map(|((schema_name, property_name), old, new)| {
+                    format!(
+                        "{} {}: {} -> {}",
+                        schema_name,
+                        property_name,
+                        old.unwrap_or(&missing),
+                        new.unwrap_or(&missing)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ))
     }
 
     pub async fn reset_recursively(
         schema_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
-        let proxy = schema::SchemaProxy::new(&conn).await?;
-        let success = proxy.reset_all(&schema_name).await?;
-        let value = serde_json::json!({
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let success = backend.reset_all(&schema_name).await?;
+        Ok(Output::Data(serde_json::json!({
             "success": success,
-        });
-        let val = serde_json::to_string_pretty(&value)?;
-        Ok(val)
+        })))
     }
 
     pub async fn reset(
         schema_name: String,
         property_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
-        let proxy = property::PropertyProxy::new(&conn).await?;
-        let success = proxy.reset(&schema_name, &property_name).await?;
-
-        let value = serde_json::json!({
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let success = backend.reset(&schema_name, &property_name).await?;
+        Ok(Output::Data(serde_json::json!({
             "success": success,
-        });
+        })))
+    }
 
-        let val = serde_json::to_string_pretty(&value)?;
-        Ok(val)
+    pub async fn get_property(
+        schema_name: String,
+        property_name: String,
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let info = backend.property(&schema_name, &property_name, None).await?;
+        if info.deprecated {
+            warn_deprecated(&property_name, &info);
+        }
+
+        Ok(Output::Data(value_field(&info.current, format)))
     }
 
-    fn convert_property_to_serde(value: (bool, OwnedValue)) -> anyhow::Result<serde_json::Value> {
-        if value.0 {
-            Ok(serde_json::Value::Null)
-        } else {
-            Ok(serde_json::to_value(value.1)?)
+    /// Property values are printed in the GVariant text format under `text` and `table`
+    fn value_field(value: &Nullable, format: OutputFormat) -> serde_json::Value {
+        match format.reads_json() {
+            true => value.to_json(),
+            false => value.to_string().into(),
+        }
+    }
+
+    pub async fn set_property(
+        schema_name: String,
+        property_name: String,
+        value: String,
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let info = backend.property(&schema_name, &property_name, None).await?;
+        if info.deprecated {
+            warn_deprecated(&property_name, &info);
+        }
+        if !info.writable {
+            return Err(anyhow::Error::new(PropertyError::NotWritable)
+                .context(format!("While trying to write to {}", property_name)));
+        }
+
+        let value = match format.reads_json() {
+            false => Value::parse(&value, &info.signature),
+            true => Value::from_json(&serde_json::from_str(&value)?, &info.signature),
+        }
+        .map_err(|err| {
+            err.context(format!(
+                "While parsing the value of {} as `{}`",
+                property_name,
+                info.signature.as_str()
+            ))
+        })?;
+
+        backend
+            .set(&schema_name, &property_name, value.into())
+            .await?;
+        Ok(Output::None)
+    }
+
+    fn warn_deprecated(property_name: &str, info: &PropertyInfo) {
+        match info.replaced_by.is_empty() {
+            true => eprintln!(
+                "warning: property `{}` is deprecated: {}",
+                property_name, info.deprecation
+            ),
+            false => eprintln!(
+                "warning: property `{}` is deprecated and forwarded to `{}`: {}",
+                property_name, info.replaced_by, info.deprecation
+            ),
         }
     }
 
     pub async fn monitor_property(
         schema_name: String,
         property_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
         use futures_util::StreamExt;
-        let proxy = property::PropertyProxy::new(&conn).await?;
-        let mut signal: property::property_changedStream<'_> = proxy
-            .receive_property_changed_with_args(&[(0, &schema_name), (1, &property_name)])
+        let proxy = property::PropertyProxy::new(backend.connection("property monitor")?).await?;
+        let mut signal: property::property_changed_v2Stream<'_> = proxy
+            .receive_property_changed_v2_with_args(&[(0, &schema_name), (1, &property_name)])
             .await?;
 
-        let mut current_property =
-            convert_property_to_serde(proxy.metadata(&schema_name, &property_name).await?.6)?;
-
         while let Some(change) = signal.next().await {
-            let new_value =
-                convert_property_to_serde(proxy.metadata(&schema_name, &property_name).await?.6)?;
-            let json = serde_json::json!({
-                "schema": &schema_name,
-                "property": &property_name,
-                "from": &current_property,
-                "to": &new_value,
-            });
-            current_property = new_value;
-            println!("{}", serde_json::to_string_pretty(&json)?);
+            let args = change.args()?;
+            let event = match format {
+                OutputFormat::Text | OutputFormat::Table => format!(
+                    "{} {}: {} -> {}",
+                    schema_name,
+                    property_name,
+                    args.previous(),
+                    args.value()
+                ),
+                format => Output::Data(serde_json::json!({
+                    "schema": &schema_name,
+                    "property": &property_name,
+                    "from": value_field(args.previous(), format),
+                    "to": value_field(args.value(), format),
+                }))
+                .render(format)?,
+            };
+            println!("{}", event);
         }
 
         anyhow::bail!("Property Stream has ended")
@@ -476,149 +1724,863 @@ mod cli {
     pub async fn montior_trigger(
         schema_name: String,
         trigger_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
-        let mut proxy = trigger::TriggerProxy::new(&conn).await?;
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let proxy = trigger::TriggerProxy::new(backend.connection("trigger monitor")?).await?;
         let mut trigger = proxy
             .receive_trigger_invoked_with_args(&[(0, &schema_name), (1, &trigger_name)])
             .await?;
-        let mut metadata = proxy.metadata(&schema_name, &trigger_name).await?;
+        let (_, signature) = proxy.metadata(&schema_name, &trigger_name).await?;
 
         while let Some(trigger) = trigger.next().await {
-            let value = serde_json::to_value(trigger.args()?.value())?;
-            let json = serde_json::json!({
-                "schema": &schema_name,
-                "trigger": &trigger_name,
-                "signature": &metadata.1,
-                "args": value,
-            });
-            println!("{}", serde_json::to_string_pretty(&json)?);
+            let args = Nullable::from(Some(OwnedValue::from(trigger.args()?.value())));
+            let event = match format {
+                OutputFormat::Text | OutputFormat::Table => {
+                    format!("{} {}: {}", schema_name, trigger_name, args)
+                }
+                format => Output::Data(serde_json::json!({
+                    "schema": &schema_name,
+                    "trigger": &trigger_name,
+                    "signature": &signature,
+                    "args": value_field(&args, format),
+                }))
+                .render(format)?,
+            };
+            println!("{}", event);
         }
         anyhow::bail!("Trigger steam has ended")
     }
 
-    pub async fn metadata_schema(
+    pub async fn invoke_trigger(
         schema_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
-        let proxy = schema::SchemaProxy::new(&conn).await?;
-        let metadata = proxy.metadata(&schema_name).await?;
+        trigger_name: String,
+        payload: String,
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        // Triggers only exist as signals, there is nobody to deliver them to offline.
+        let proxy = trigger::TriggerProxy::new(backend.connection("trigger invoke")?).await?;
+        let (_, signature) = proxy.metadata(&schema_name, &trigger_name).await?;
+
+        let payload = match format.reads_json() {
+            false => Value::parse(&payload, &signature),
+            true => Value::from_json(&serde_json::from_str(&payload)?, &signature),
+        }
+        .map_err(|err| {
+            err.context(format!(
+                "While parsing the payload of {} as `{}`",
+                trigger_name,
+                signature.as_str()
+            ))
+        })?;
+        let payload = payload.get_inner().ok_or(ValueError::Null).map_err(|err| {
+            anyhow::Error::new(err).context("Triggers cannot be invoked with `nothing`")
+        })?;
+
+        proxy
+            .invoke_trigger(&schema_name, &trigger_name, payload)
+            .await?;
+        Ok(Output::None)
+    }
+
+    /// Prints events as NDJSON unless `text` or `table` is asked for, `json` included
+    pub async fn watch(
+        schema_prefix: Option<String>,
+        include_triggers: bool,
+        format: Option<OutputFormat>,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let format = match format {
+            Some(format @ (OutputFormat::Text | OutputFormat::Table)) => format,
+            _ => OutputFormat::Ndjson,
+        };
+        let conn = backend.connection("watch")?;
+        let prefix = schema_prefix.unwrap_or_default();
+        let property_proxy = property::PropertyProxy::new(conn).await?;
+        let trigger_proxy = trigger::TriggerProxy::new(conn).await?;
+        let mut changes = property_proxy.receive_property_changed_v2().await?;
+        let mut invocations = match include_triggers {
+            true => Some(trigger_proxy.receive_trigger_invoked_by().await?),
+            false => None,
+        };
+
+        loop {
+            let event = tokio::select! {
+                change = changes.next() => {
+                    let Some(change) = change else {
+                        anyhow::bail!("Property Stream has ended");
+                    };
+                    let args = change.args()?;
+                    if !args.schema_name().starts_with(&prefix) {
+                        continue;
+                    }
+                    match format {
+                        OutputFormat::Text | OutputFormat::Table => format!(
+                            "{} {} {}: {} -> {} by {}",
+                            timestamp(),
+                            args.schema_name(),
+                            args.key_name(),
+                            args.previous(),
+                            args.value(),
+                            args.sender()
+                        ),
+                        format => Output::Data(serde_json::json!({
+                            "timestamp": timestamp(),
+                            "serial": args.serial(),
+                            "schema": args.schema_name(),
+                            "property": args.key_name(),
+                            "old": value_field(args.previous(), format),
+                            "new": value_field(args.value(), format),
+                            "sender": args.sender(),
+                        }))
+                        .render(format)?,
+                    }
+                }
+                Some(invocation) = async {
+                    match &mut invocations {
+                        Some(invocations) => invocations.next().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let args = invocation.args()?;
+                    if !args.schema_name().starts_with(&prefix) {
+                        continue;
+                    }
+                    let value = Nullable::from(Some(OwnedValue::from(args.value())));
+                    match format {
+                        OutputFormat::Text | OutputFormat::Table => format!(
+                            "{} {} {} invoked: {} by {}",
+                            timestamp(),
+                            args.schema_name(),
+                            args.trigger_name(),
+                            value,
+                            args.sender()
+                        ),
+                        format => Output::Data(serde_json::json!({
+                            "timestamp": timestamp(),
+                            "schema": args.schema_name(),
+                            "trigger": args.trigger_name(),
+                            "args": value_field(&value, format),
+                            "sender": args.sender(),
+                        }))
+                        .render(format)?,
+                    }
+                }
+            };
+            println!("{}", event);
+        }
+    }
+
+    /// The current time as RFC 3339 in UTC, e.g. `2023-10-18T09:41:07.512Z`
+    fn timestamp() -> String {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let seconds = now.as_secs() as i64;
+        let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+        // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time % 3600 / 60,
+            time % 60,
+            now.subsec_millis()
+        )
+    }
 
-        let json = serde_json::json!({
+    pub async fn metadata_schema(schema_name: String, backend: &Backend) -> anyhow::Result<Output> {
+        let metadata = backend.schema(&schema_name).await?;
+
+        Ok(Output::Data(serde_json::json!({
             "name": &metadata.0,
             "version": &metadata.1,
             "triggers": &metadata.2,
             "properties": &metadata.3,
-        });
-
-        Ok(serde_json::to_string_pretty(&json)?)
+        })))
     }
 
     pub async fn metadata_trigger(
         schema_name: String,
         trigger_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
-        let mut proxy = trigger::TriggerProxy::new(&conn).await?;
-        let mut metadata = proxy.metadata(&schema_name, &trigger_name).await?;
-        let json = serde_json::json!({
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let metadata = backend.trigger(&schema_name, &trigger_name).await?;
+        Ok(Output::Data(serde_json::json!({
             "name": &metadata.0,
             "signature": &metadata.1,
-        });
-
-        return Ok(serde_json::to_string_pretty(&json)?);
+        })))
     }
 
     pub async fn metadata_property(
         schema_name: String,
         property_name: String,
-        conn: &zbus::Connection,
-    ) -> anyhow::Result<String> {
-        let proxy = property::PropertyProxy::new(&conn).await?;
-        let info = proxy.metadata(&schema_name, &property_name).await?;
-
-        let current = convert_property_to_serde(info.6)?;
-
-        let value = serde_json::json!({
-            "name": info.1,
-            "writable": info.0,
-            "about": info.3,
-            "long_about": info.2,
-            "sos": info.4,
-            "signature": info.5,
+        locale: Option<String>,
+        format: OutputFormat,
+        backend: &Backend,
+    ) -> anyhow::Result<Output> {
+        let locale = locale.unwrap_or_else(gludconfig::locale::current_locale);
+        let info = backend
+            .property(&schema_name, &property_name, Some(&locale))
+            .await?;
+
+        if info.deprecated {
+            warn_deprecated(&property_name, &info);
+        }
+
+        let current = value_field(&info.current, format);
+
+        Ok(Output::Data(serde_json::json!({
+            "name": info.name,
+            "writable": info.writable,
+            "about": info.summary,
+            "long_about": info.description,
+            "sos": info.show_in_settings,
+            "signature": info.signature,
             "value": current,
-        });
+            "group": info.group,
+            "order": info.order,
+            "widget": info.widget,
+            "advanced": info.advanced,
+            "deprecated": info.deprecated,
+            "deprecation": info.deprecation,
+            "replaced_by": info.replaced_by,
+            "enabled": info.enabled,
+            "enabled_when": info.enabled_when,
+            "computed": info.computed,
+            "choices": info
+                .choices
+                .iter()
+                .map(|choice| value_field(choice, format))
+                .collect::<Vec<_>>(),
+        })))
+    }
+}
+
+#[cfg(feature = "tui")]
+mod tui {
+    use crossterm::{
+        event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    };
+    use futures_util::StreamExt;
+    use gludconfig::{
+        error::PropertyError,
+        property::PropertyInfo,
+        value::{Nullable, Value},
+    };
+    use ratatui::{
+        backend::CrosstermBackend,
+        layout::{Constraint, Direction, Layout, Rect},
+        style::{Color, Modifier, Style},
+        text::{Line, Span},
+        widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+        Frame, Terminal,
+    };
 
-        let val = serde_json::to_string_pretty(&value)?;
-        Ok(val)
+    use crate::{
+        backend::{Backend, SchemaSummary},
+        property,
+    };
+
+    const HELP: &str =
+        "↑↓ move  ←→/tab switch pane  enter edit  r reset  q quit | editing: ↑↓ choices  enter apply  esc cancel";
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Focus {
+        Schemas,
+        Properties,
+    }
+
+    struct App {
+        schemas: Vec<SchemaSummary>,
+        schema_state: ListState,
+        /// Properties of the selected schema, `None` where the metadata could not be fetched
+        properties: Vec<(String, Option<PropertyInfo>)>,
+        property_state: ListState,
+        focus: Focus,
+        /// Text of the value being edited, and which choice it was last set to
+        editor: Option<(String, Option<usize>)>,
+        /// Last message shown in the status line, and whether it is an error
+        status: Option<(String, bool)>,
+    }
+
+    /// Puts the terminal back into its normal state, also when the editor fails
+    struct TerminalGuard;
+
+    impl Drop for TerminalGuard {
+        fn drop(&mut self) {
+            let _ = disable_raw_mode();
+            let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+        }
+    }
+
+    /// Runs the full screen editor until the user quits
+    pub async fn run(backend: &Backend) -> anyhow::Result<()> {
+        let proxy = property::PropertyProxy::new(backend.connection("tui")?).await?;
+        let mut changes = proxy.receive_property_changed().await?;
+
+        let mut app = App {
+            schemas: backend.schemas().await?,
+            schema_state: ListState::default(),
+            properties: vec![],
+            property_state: ListState::default(),
+            focus: Focus::Schemas,
+            editor: None,
+            status: None,
+        };
+        app.schemas.sort_by(|a, b| a.0.cmp(&b.0));
+        if !app.schemas.is_empty() {
+            app.select_schema(0, backend).await;
+        }
+
+        enable_raw_mode()?;
+        let _guard = TerminalGuard;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+        let mut events = EventStream::new();
+
+        loop {
+            terminal.draw(|frame| app.draw(frame))?;
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(Ok(Event::Key(key))) if key.kind != KeyEventKind::Release => {
+                        if !app.on_key(key, backend).await {
+                            return Ok(());
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err.into()),
+                    None => return Ok(()),
+                },
+                change = changes.next() => {
+                    let Some(change) = change else {
+                        anyhow::bail!("Property Stream has ended");
+                    };
+                    let args = change.args()?;
+                    app.on_change(args.schema_name(), args.key_name(), backend).await;
+                }
+            }
+        }
+    }
+
+    impl App {
+        fn selected_schema(&self) -> Option<&SchemaSummary> {
+            self.schemas.get(self.schema_state.selected()?)
+        }
+
+        fn selected_property(&self) -> Option<&PropertyInfo> {
+            self.properties
+                .get(self.property_state.selected()?)?
+                .1
+                .as_ref()
+        }
+
+        async fn select_schema(&mut self, idx: usize, backend: &Backend) {
+            self.schema_state.select(Some(idx));
+            self.property_state
+                .select(Some(0).filter(|_| !self.schemas[idx].3.is_empty()));
+            let (schema_name, _, _, property_names) = self.schemas[idx].clone();
+            self.properties.clear();
+            for property_name in property_names {
+                let info = backend.property(&schema_name, &property_name, None).await;
+                if let Err(err) = &info {
+                    self.error(err);
+                }
+                self.properties.push((property_name, info.ok()));
+            }
+        }
+
+        async fn refresh_property(&mut self, idx: usize, backend: &Backend) {
+            let Some((schema_name, ..)) = self.selected_schema() else {
+                return;
+            };
+            let schema_name = schema_name.clone();
+            let property_name = self.properties[idx].0.clone();
+            match backend.property(&schema_name, &property_name, None).await {
+                Ok(info) => self.properties[idx].1 = Some(info),
+                Err(err) => self.error(&err),
+            }
+        }
+
+        /// Re-reads a property another client changed, if it is on screen
+        async fn on_change(&mut self, schema_name: &str, key_name: &str, backend: &Backend) {
+            if self.selected_schema().map(|schema| schema.0.as_str()) != Some(schema_name) {
+                return;
+            }
+            // Computed properties change along with their inputs, so everything is re-read.
+            for idx in 0..self.properties.len() {
+                self.refresh_property(idx, backend).await;
+            }
+            self.status = Some((format!("{} changed", key_name), false));
+        }
+
+        fn error(&mut self, err: &anyhow::Error) {
+            self.status = Some((format!("{:#}", err), true));
+        }
+
+        /// Handles a key press, returns false once the user quits
+        async fn on_key(&mut self, key: KeyEvent, backend: &Backend) -> bool {
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                return false;
+            }
+            if self.editor.is_some() {
+                self.on_editor_key(key.code, backend).await;
+                return true;
+            }
+
+            match (key.code, self.focus) {
+                (KeyCode::Char('q') | KeyCode::Esc, _) => return false,
+                (KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Char('h' | 'l'), _) => {
+                    self.focus = match self.focus {
+                        Focus::Schemas => Focus::Properties,
+                        Focus::Properties => Focus::Schemas,
+                    }
+                }
+                (KeyCode::Up | KeyCode::Char('k'), Focus::Schemas) => {
+                    if let Some(idx) = step(self.schema_state.selected(), self.schemas.len(), -1) {
+                        self.select_schema(idx, backend).await;
+                    }
+                }
+                (KeyCode::Down | KeyCode::Char('j'), Focus::Schemas) => {
+                    if let Some(idx) = step(self.schema_state.selected(), self.schemas.len(), 1) {
+                        self.select_schema(idx, backend).await;
+                    }
+                }
+                (KeyCode::Up | KeyCode::Char('k'), Focus::Properties) => {
+                    self.property_state.select(step(
+                        self.property_state.selected(),
+                        self.properties.len(),
+                        -1,
+                    ))
+                }
+                (KeyCode::Down | KeyCode::Char('j'), Focus::Properties) => {
+                    self.property_state.select(step(
+                        self.property_state.selected(),
+                        self.properties.len(),
+                        1,
+                    ))
+                }
+                (KeyCode::Enter, Focus::Schemas) => self.focus = Focus::Properties,
+                (KeyCode::Enter | KeyCode::Char('e'), Focus::Properties) => {
+                    if let Some(info) = self.selected_property() {
+                        let choice = info.choices.iter().position(|c| *c == info.current);
+                        self.editor = Some((info.current.to_string(), choice));
+                    }
+                }
+                (KeyCode::Char('r'), Focus::Properties) => self.reset(backend).await,
+                _ => {}
+            }
+            true
+        }
+
+        async fn on_editor_key(&mut self, code: KeyCode, backend: &Backend) {
+            let Some(choices) = self.selected_property().map(|info| info.choices.clone()) else {
+                self.editor = None;
+                return;
+            };
+            let Some((text, choice)) = &mut self.editor else {
+                return;
+            };
+            match code {
+                KeyCode::Esc => self.editor = None,
+                KeyCode::Enter => self.apply(backend).await,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Up | KeyCode::Down if !choices.is_empty() => {
+                    let idx = match (*choice, code) {
+                        (None, _) => 0,
+                        (Some(idx), KeyCode::Up) => (idx + choices.len() - 1) % choices.len(),
+                        (Some(idx), _) => (idx + 1) % choices.len(),
+                    };
+                    *choice = Some(idx);
+                    *text = choices[idx].to_string();
+                }
+                _ => {}
+            }
+        }
+
+        /// Validates the edited text against the property's signature and writes it
+        async fn apply(&mut self, backend: &Backend) {
+            let (Some(idx), Some((text, _))) = (self.property_state.selected(), &self.editor)
+            else {
+                return;
+            };
+            let Some((schema_name, ..)) = self.selected_schema() else {
+                return;
+            };
+            let (Some(info), schema_name) = (&self.properties[idx].1, schema_name.clone()) else {
+                return;
+            };
+
+            let result = match info.writable {
+                false => Err(PropertyError::NotWritable.into()),
+                true => Value::parse(text, &info.signature),
+            };
+            let result = match result {
+                Ok(value) => backend.set(&schema_name, &info.name, value.into()).await,
+                Err(err) => Err(err.context(format!(
+                    "While parsing the value of {} as `{}`",
+                    info.name,
+                    info.signature.as_str()
+                ))),
+            };
+            match result {
+                Ok(()) => {
+                    self.status = Some((format!("{} = {}", info.name, text), false));
+                    self.editor = None;
+                    self.refresh_property(idx, backend).await;
+                }
+                Err(err) => self.error(&err),
+            }
+        }
+
+        async fn reset(&mut self, backend: &Backend) {
+            let (Some(idx), Some((schema_name, ..))) =
+                (self.property_state.selected(), self.selected_schema())
+            else {
+                return;
+            };
+            let schema_name = schema_name.clone();
+            let property_name = self.properties[idx].0.clone();
+            match backend.reset(&schema_name, &property_name).await {
+                Ok(true) => self.status = Some((format!("{} was reset", property_name), false)),
+                Ok(false) => {
+                    self.status = Some((format!("{} could not be reset", property_name), true))
+                }
+                Err(err) => self.error(&err),
+            }
+            self.refresh_property(idx, backend).await;
+        }
+
+        fn draw(&mut self, frame: &mut Frame) {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(frame.size());
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(25),
+                    Constraint::Percentage(25),
+                    Constraint::Percentage(50),
+                ])
+                .split(rows[0]);
+
+            let schemas = List::new(
+                self.schemas
+                    .iter()
+                    .map(|schema| ListItem::new(schema.0.as_str()))
+                    .collect::<Vec<_>>(),
+            );
+            let schemas = self.list(schemas, "Schemas", Focus::Schemas);
+            frame.render_stateful_widget(schemas, columns[0], &mut self.schema_state);
+
+            let properties = List::new(
+                self.properties
+                    .iter()
+                    .map(|(name, info)| match info {
+                        Some(info) if !info.writable => {
+                            ListItem::new(name.as_str()).style(Style::default().fg(Color::DarkGray))
+                        }
+                        _ => ListItem::new(name.as_str()),
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            let properties = self.list(properties, "Properties", Focus::Properties);
+            frame.render_stateful_widget(properties, columns[1], &mut self.property_state);
+
+            self.draw_details(frame, columns[2]);
+
+            let status = match &self.status {
+                Some((message, true)) => {
+                    Line::styled(message.as_str(), Style::default().fg(Color::Red))
+                }
+                Some((message, false)) => Line::raw(message.as_str()),
+                None => Line::raw(""),
+            };
+            frame.render_widget(
+                Paragraph::new(vec![
+                    status,
+                    Line::styled(HELP, Style::default().fg(Color::DarkGray)),
+                ]),
+                rows[1].inner(&ratatui::layout::Margin::new(1, 0)),
+            );
+        }
+
+        fn list<'a>(&self, list: List<'a>, title: &'a str, focus: Focus) -> List<'a> {
+            let border = match self.focus == focus && self.editor.is_none() {
+                true => Style::default().fg(Color::Yellow),
+                false => Style::default(),
+            };
+            list.block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border)
+                    .title(title),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        }
+
+        fn draw_details(&self, frame: &mut Frame, area: Rect) {
+            let block = Block::default().borders(Borders::ALL).title("Details");
+            let Some(info) = self.selected_property() else {
+                frame.render_widget(block, area);
+                return;
+            };
+
+            let label = |name: &'static str| {
+                Span::styled(name, Style::default().add_modifier(Modifier::BOLD))
+            };
+            let mut lines = vec![
+                Line::from(vec![label("name: "), Span::raw(info.name.as_str())]),
+                Line::from(vec![
+                    label("signature: "),
+                    Span::raw(info.signature.as_str()),
+                ]),
+                Line::from(vec![
+                    label("writable: "),
+                    Span::raw(info.writable.to_string()),
+                ]),
+                Line::from(vec![
+                    label("current: "),
+                    Span::raw(info.current.to_string()),
+                ]),
+                Line::from(vec![
+                    label("default: "),
+                    Span::raw(info.default.to_string()),
+                ]),
+            ];
+            if !info.choices.is_empty() {
+                let choices = info
+                    .choices
+                    .iter()
+                    .map(Nullable::to_string)
+                    .collect::<Vec<_>>();
+                lines.push(Line::from(vec![
+                    label("choices: "),
+                    Span::raw(choices.join(", ")),
+                ]));
+            }
+            if !info.computed.is_empty() {
+                lines.push(Line::from(vec![
+                    label("computed: "),
+                    Span::raw(info.computed.as_str()),
+                ]));
+            }
+            if !info.enabled {
+                lines.push(Line::styled(
+                    format!("disabled, enabled when {}", info.enabled_when),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            if info.deprecated {
+                let mut deprecation = format!("deprecated: {}", info.deprecation);
+                if !info.replaced_by.is_empty() {
+                    deprecation.push_str(&format!(" (replaced by {})", info.replaced_by));
+                }
+                lines.push(Line::styled(
+                    deprecation,
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            lines.push(Line::raw(""));
+            lines.push(Line::raw(info.summary.as_str()));
+            if !info.description.is_empty() {
+                lines.push(Line::raw(""));
+                lines.push(Line::raw(info.description.as_str()));
+            }
+
+            let [details, editor] = match &self.editor {
+                Some(_) => {
+                    let split = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(3)])
+                        .split(area);
+                    [split[0], split[1]]
+                }
+                None => [area, Rect::default()],
+            };
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .block(block)
+                    .wrap(Wrap { trim: false }),
+                details,
+            );
+
+            if let Some((text, _)) = &self.editor {
+                let title = format!("New value `{}`", info.signature.as_str());
+                frame.render_widget(
+                    Paragraph::new(text.as_str()).block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Yellow))
+                            .title(title),
+                    ),
+                    editor,
+                );
+                let width = text.chars().count() as u16;
+                frame.set_cursor(
+                    (editor.x + 1 + width).min(editor.right().saturating_sub(2)),
+                    editor.y + 1,
+                );
+            }
+        }
+    }
+
+    /// Moves a list selection by `delta`, staying within `len`
+    fn step(selected: Option<usize>, len: usize, delta: isize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let idx = selected.map_or(0, |idx| idx.saturating_add_signed(delta));
+        Some(idx.min(len - 1))
     }
 }
 
 #[cfg(feature = "cli")]
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> anyhow::Result<()> {
+async fn main() {
     use clap::Parser;
+
+    let cli = cli::GludCli::parse();
+    let format = cli.format.unwrap_or_default();
+
+    match run(cli.command, cli.format, cli.offline)
+        .await
+        .and_then(|output| output.render(format))
+    {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            std::process::exit(cli::ExitCode::of(&err) as i32)
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+async fn run(
+    cmd: cli::Command,
+    requested_format: Option<cli::OutputFormat>,
+    offline: bool,
+) -> anyhow::Result<cli::Output> {
+    use backend::Backend;
     use gludconfig::storage::Storage;
 
-    let conn = zbus::Connection::session().await?;
-    let cmd = cli::GludCli::parse();
+    let format = requested_format.unwrap_or_default();
 
-    let output = match cmd {
-        cli::GludCli::Property(cmd) => match cmd {
+    let cmd = match cmd {
+        cli::Command::GenCode {
+            blocking,
+            schema,
+            name,
+        } => {
+            let mut storage = Storage::new().await?;
+            let mut schema = storage.get_schema(schema).await?;
+
+            let mut code = vec![];
+            cli::generate_for_schema(&mut schema, &name, blocking, &mut code)?;
+            return Ok(cli::Output::Raw(String::from_utf8(code)?));
+        }
+        cli::Command::Completions { shell } => return cli::completion_script(shell),
+        cli::Command::Complete { words } => return Ok(cli::complete(words).await),
+        cmd => cmd,
+    };
+
+    let backend = Backend::connect(offline).await?;
+
+    match cmd {
+        cli::Command::Property(cmd) => match cmd {
             cli::PropertyCommand::Metadata {
                 schema_name,
                 property_name,
-            } => map_err_to_str(cli::metadata_property(schema_name, property_name, &conn).await),
+                locale,
+            } => cli::metadata_property(schema_name, property_name, locale, format, &backend).await,
+            cli::PropertyCommand::Get {
+                schema_name,
+                property_name,
+            } => cli::get_property(schema_name, property_name, format, &backend).await,
+            cli::PropertyCommand::Set {
+                schema_name,
+                property_name,
+                value,
+            } => cli::set_property(schema_name, property_name, value, format, &backend).await,
             cli::PropertyCommand::Reset {
                 schema_name,
                 property_name,
-            } => map_err_to_str(cli::reset(schema_name, property_name, &conn).await),
+            } => cli::reset(schema_name, property_name, &backend).await,
             cli::PropertyCommand::Monitor {
                 schema_name,
                 property_name,
-            } => map_err_to_str(cli::monitor_property(schema_name, property_name, &conn).await),
+            } => cli::monitor_property(schema_name, property_name, format, &backend).await,
         },
-        cli::GludCli::Schema(cmd) => match cmd {
-            cli::SchemaCommand::ListAll => map_err_to_str(cli::list_schemas(&conn).await),
+        cli::Command::Schema(cmd) => match cmd {
+            cli::SchemaCommand::ListAll => cli::list_schemas(&backend).await,
             cli::SchemaCommand::Metadata { schema_name } => {
-                map_err_to_str(cli::metadata_schema(schema_name, &conn).await)
+                cli::metadata_schema(schema_name, &backend).await
             }
             cli::SchemaCommand::ResetRecursively { schema_name } => {
-                map_err_to_str(cli::reset_recursively(schema_name, &conn).await)
+                cli::reset_recursively(schema_name, &backend).await
             }
+            cli::SchemaCommand::Register {
+                file,
+                override_owner,
+            } => cli::register_schema(file, override_owner, &backend).await,
+            cli::SchemaCommand::Delete {
+                schema_name,
+                yes,
+                override_owner,
+            } => cli::delete_schema(schema_name, yes, override_owner, &backend).await,
         },
-        cli::GludCli::TriggerCommand(cmd) => match cmd {
+        cli::Command::TriggerCommand(cmd) => match cmd {
             cli::TriggerCommand::Monitor {
                 schema_name,
                 trigger_name,
-            } => map_err_to_str(cli::montior_trigger(schema_name, trigger_name, &conn).await),
+            } => cli::montior_trigger(schema_name, trigger_name, format, &backend).await,
             cli::TriggerCommand::Metadata {
                 schema_name,
                 trigger_name,
-            } => map_err_to_str(cli::metadata_trigger(schema_name, trigger_name, &conn).await),
+            } => cli::metadata_trigger(schema_name, trigger_name, &backend).await,
+            cli::TriggerCommand::Invoke {
+                schema_name,
+                trigger_name,
+                payload,
+            } => cli::invoke_trigger(schema_name, trigger_name, payload, format, &backend).await,
         },
-        cli::GludCli::GenCode {
-            blocking,
-            schema,
-            name,
-        } => {
-            let mut storage = Storage::new().await?;
-            let mut schema = storage.get_schema(schema).await?;
-
-            cli::generate_for_schema(&mut schema, &name, blocking, &mut std::io::stdout())?;
-
-            map_err_to_str::<_, &str>(Ok(""))
-        }
-    };
-
-    println!("{}", output);
-    Ok(())
-}
-
-fn map_err_to_str<T: Display, E: Display>(err: Result<T, E>) -> String {
-    match err {
-        Ok(val) => format!("{}", val),
-        Err(err) => format!("{}", err),
+        cli::Command::Watch {
+            schema_prefix,
+            include_triggers,
+        } => cli::watch(schema_prefix, include_triggers, requested_format, &backend).await,
+        cli::Command::Dump { schema_prefix } => cli::dump(schema_prefix, &backend).await,
+        cli::Command::Load { file } => cli::load(file, &backend).await,
+        cli::Command::Diff {
+            schema_prefix,
+            backup,
+            database,
+        } => cli::diff(schema_prefix, backup, database, format, &backend).await,
+        #[cfg(feature = "tui")]
+        cli::Command::Tui => tui::run(&backend).await.map(|()| cli::Output::None),
+        cli::Command::GenCode { .. }
+        | cli::Command::Completions { .. }
+        | cli::Command::Complete { .. } => unreachable!("handled without a backend"),
     }
 }
 
@@ -1,35 +1,49 @@
 #[cfg(feature = "dbus")]
 mod interface {
-    use std::{collections::BTreeMap, sync::Arc};
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
 
     use gludconfig::{
-        error::ZbusError,
-        property::Property,
+        definition::{Format, SchemaDefinition},
+        error::{DaemonError, ZbusError},
+        keyfile::KeyFile,
+        policy::{Access, Caller, Owner, Policy},
+        property::{PropertyInfo, PropertyInfoV0},
         schema::Schema,
+        session::Session,
         storage::{into_zbus_error, Storage},
         trigger::Trigger,
         value::Nullable,
     };
 
-    use zbus::{dbus_interface, names::BusName, SignalContext};
+    use zbus::{dbus_interface, names::BusName, MessageHeader, SignalContext};
     use zvariant::{dbus, from_slice, OwnedSignature, OwnedValue, Signature};
 
     pub struct PropertyInterface {
         pub storage: Arc<Storage>,
+        pub sessions: Sessions,
+        pub policy: Arc<Policy>,
     }
 
+    /// Delayed-apply sessions by sender and schema name, see `PropertyInterface::delay`
+    pub type Sessions = Arc<Mutex<BTreeMap<(String, String), Session>>>;
+
     pub struct TriggerInterface {
         pub storage: Arc<Storage>,
+        pub policy: Arc<Policy>,
     }
 
     pub struct SchemaInterface {
         pub storage: Arc<Storage>,
+        pub policy: Arc<Policy>,
     }
 
     #[dbus_interface(name = "org.glud.GludConfig.Schema")]
     impl SchemaInterface {
         #[dbus_interface(name = "all")]
-        async fn all(&self) -> zbus::fdo::Result<Vec<SchemaInfo>> {
+        async fn all(&self) -> Result<Vec<SchemaInfo>, DaemonError> {
             Ok(self
                 .storage
                 .fetch_all()
@@ -40,40 +54,209 @@ mod interface {
         }
 
         #[dbus_interface(name = "register")]
-        async fn register(&self, data: Vec<u8>) -> zbus::fdo::Result<()> {
+        async fn register(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            data: Vec<u8>,
+        ) -> Result<(), DaemonError> {
             let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
-            let schema: Schema = from_slice(&data, ctx).map_err(into_zbus_error)?;
-            self.storage.new_schema(&schema).await
+            let mut schema: Schema = from_slice(&data, ctx).map_err(into_zbus_error)?;
+            // Decoded blobs skip `SchemaBuilder::build`, so check them the same way
+            schema.validate().map_err(DaemonError::from)?;
+            schema.recompute();
+            self.register_owned(conn, &header, schema, false).await
+        }
+
+        /// Registers a schema from a textual definition, `format` being `toml` or `json`
+        #[dbus_interface(name = "register_definition")]
+        async fn register_definition(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            definition: String,
+            format: String,
+        ) -> Result<(), DaemonError> {
+            let schema = format
+                .parse::<Format>()
+                .map_err(anyhow::Error::new)
+                .and_then(|format| SchemaDefinition::parse(&definition, format))
+                .and_then(SchemaDefinition::into_schema)
+                .map_err(DaemonError::from)?;
+            self.register_owned(conn, &header, schema, false).await
+        }
+
+        /// Replaces a schema definition even if another application registered it, for admins
+        #[dbus_interface(name = "register_definition_override")]
+        async fn register_definition_override(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            definition: String,
+            format: String,
+        ) -> Result<(), DaemonError> {
+            let schema = format
+                .parse::<Format>()
+                .map_err(anyhow::Error::new)
+                .and_then(|format| SchemaDefinition::parse(&definition, format))
+                .and_then(SchemaDefinition::into_schema)
+                .map_err(DaemonError::from)?;
+            self.register_owned(conn, &header, schema, true).await
+        }
+
+        #[dbus_interface(name = "delete")]
+        async fn delete(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> Result<(), DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Write).await?;
+            self.delete_schema(conn, &header, schema_name, false).await
+        }
+
+        /// Deletes a schema even if another application registered it, for admins
+        #[dbus_interface(name = "delete_override")]
+        async fn delete_override(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> Result<(), DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Admin).await?;
+            self.delete_schema(conn, &header, schema_name, true).await
         }
 
         #[dbus_interface(name = "metadata")]
-        async fn metadata(&self, schema_name: String) -> zbus::fdo::Result<SchemaInfo> {
+        async fn metadata(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> Result<SchemaInfo, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
             Ok(self.storage.get_schema(schema_name).await?.into())
         }
         #[dbus_interface(name = "reset_all")]
         async fn reset_all(
             &self,
             #[zbus(signal_context)] ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
             schema_name: String,
-        ) -> zbus::fdo::Result<bool> {
+        ) -> Result<bool, DaemonError> {
+            let conn = ctx.connection();
+            authorize(&self.policy, conn, &header, &schema_name, Access::Write).await?;
+            let _guard = self.storage.lock_schema(&schema_name).await;
             let mut schema = self.storage.get_schema(schema_name.clone()).await?;
-            let mut res = true;
-            for p in schema.properties_mut() {
-                if Property::reset(p) {
-                    ctx.connection()
-                        .emit_signal(
-                            Option::<&BusName<'static>>::None,
-                            "/org/glud/gludconfig/property",
-                            <PropertyInterface as ::zbus::Interface>::name(),
-                            "property_changed",
-                            &(schema_name.to_string(), p.name().to_string()),
-                        )
+            let enabled = schema.enabled_states();
+            let before = schema.values();
+            let (res, was_reset) = schema.reset_all();
+            self.storage.update_schema(&schema).await?;
+            let keys: Vec<String> = was_reset
+                .iter()
+                .flat_map(|key| schema.keys_of(key))
+                .collect();
+            emit_property_changed(
+                ctx.connection(),
+                &header,
+                &self.storage,
+                &schema,
+                &before,
+                keys,
+            )
+            .await?;
+            emit_enabled_changed(ctx.connection(), &schema, &enabled).await?;
+            Ok(res)
+        }
+    }
+
+    impl SchemaInterface {
+        /// Registers `schema` with the caller as its owner, or replaces the definition if the
+        /// schema exists. Only its owner may replace it, unless `override_owner` is set. Values of
+        /// properties that keep their name and signature survive the update.
+        async fn register_owned(
+            &self,
+            conn: &zbus::Connection,
+            header: &MessageHeader<'_>,
+            mut schema: Schema,
+            override_owner: bool,
+        ) -> Result<(), DaemonError> {
+            let access = match override_owner {
+                true => Access::Admin,
+                false => Access::Write,
+            };
+            authorize(&self.policy, conn, header, schema.name(), access).await?;
+            let _guard = self.storage.lock_schema(schema.name()).await;
+            let caller = caller_credentials(conn, header).await;
+            let name = schema.name().to_string();
+
+            let old = match self.storage.get_schema(name.clone()).await {
+                Ok(old) => old,
+                Err(DaemonError::SchemaNotFound(_)) => {
+                    self.storage
+                        .new_owned_schema(&schema, &Owner::of(&caller))
                         .await?;
-                } else {
-                    res = false;
+                    return Ok(());
                 }
+                Err(err) => return Err(err),
+            };
+            if !override_owner {
+                self.check_owner(conn, header, &caller, &name).await?;
+            }
+            let (before, enabled) = (old.values(), old.enabled_states());
+            schema.keep_values(&old);
+            self.storage.update_schema(&schema).await?;
+            // Schemas registered before owners were recorded go to the next one registering them
+            if self.storage.owner(name.clone()).await?.is_none() {
+                self.storage.set_owner(name, &Owner::of(&caller)).await?;
+            }
+
+            let keys = schema
+                .values()
+                .into_iter()
+                .filter(|(key, value)| before.get(key).is_some_and(|before| before != value))
+                .map(|(key, _)| key)
+                .collect();
+            emit_property_changed(conn, header, &self.storage, &schema, &before, keys).await?;
+            emit_enabled_changed(conn, &schema, &enabled).await?;
+            Ok(())
+        }
+
+        async fn delete_schema(
+            &self,
+            conn: &zbus::Connection,
+            header: &MessageHeader<'_>,
+            schema_name: String,
+            override_owner: bool,
+        ) -> Result<(), DaemonError> {
+            let _guard = self.storage.lock_schema(&schema_name).await;
+            // Fails with a proper error if the schema does not exist.
+            self.storage.get_schema(schema_name.clone()).await?;
+            if !override_owner {
+                let caller = caller_credentials(conn, header).await;
+                self.check_owner(conn, header, &caller, &schema_name)
+                    .await?;
+            }
+            self.storage.delete_schema(schema_name).await
+        }
+
+        /// Fails with `PermissionDenied` if another application registered `schema_name`
+        async fn check_owner(
+            &self,
+            conn: &zbus::Connection,
+            header: &MessageHeader<'_>,
+            caller: &Caller,
+            schema_name: &str,
+        ) -> Result<(), DaemonError> {
+            match self.storage.owner(schema_name.to_string()).await? {
+                Some(owner) if !owner.is(caller) => Err(DaemonError::PermissionDenied(format!(
+                    "Schema {} belongs to {}, {} may not change or delete it",
+                    schema_name,
+                    owner,
+                    describe_caller(conn, header).await
+                ))),
+                _ => Ok(()),
             }
-            Ok(res)
         }
     }
 
@@ -82,14 +265,17 @@ mod interface {
         #[dbus_interface(name = "metadata")]
         async fn metadata(
             &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
             schema_name: String,
             trigger_name: String,
-        ) -> zbus::fdo::Result<TriggerInfo> {
+        ) -> Result<TriggerInfo, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
             let mut schema = self.storage.get_schema(schema_name.clone()).await?;
             let trigger = schema
                 .into_triggers()
                 .find(|p| p.name() == &trigger_name)
-                .ok_or(Into::<zbus::fdo::Error>::into(ZbusError::TriggerNotFound(
+                .ok_or(DaemonError::from(ZbusError::TriggerNotFound(
                     &schema_name,
                     &trigger_name,
                 )))?;
@@ -100,23 +286,35 @@ mod interface {
         async fn trigger(
             &self,
             #[zbus(signal_context)] ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
             schema_name: String,
             trigger_name: String,
             value: OwnedValue,
-        ) -> zbus::fdo::Result<()> {
+        ) -> Result<(), DaemonError> {
+            let conn = ctx.connection();
+            authorize(&self.policy, conn, &header, &schema_name, Access::Write).await?;
             let mut schema = self.storage.get_schema(schema_name.clone()).await?;
             let trigger = schema
                 .triggers()
                 .find(|p| p.name() == &trigger_name)
-                .ok_or(Into::<zbus::fdo::Error>::into(ZbusError::TriggerNotFound(
+                .ok_or(DaemonError::from(ZbusError::TriggerNotFound(
                     &schema_name,
                     &trigger_name,
                 )))?;
             if trigger.matches(&value) {
-                Self::trigger_invoked(&ctx, schema_name, trigger.name().to_string(), value).await?;
+                let trigger_name = trigger.name().to_string();
+                let sender = describe_caller(ctx.connection(), &header).await;
+                Self::trigger_invoked(
+                    &ctx,
+                    schema_name.clone(),
+                    trigger_name.clone(),
+                    value.clone(),
+                )
+                .await?;
+                Self::trigger_invoked_by(&ctx, schema_name, trigger_name, value, sender).await?;
                 return Ok(());
             } else {
-                return Err(zbus::fdo::Error::Failed(format!(
+                return Err(DaemonError::SignatureMismatch(format!(
                     "The signature of the trigger and the provided value dont match"
                 )));
             }
@@ -129,6 +327,16 @@ mod interface {
             trigger: String,
             value: OwnedValue,
         ) -> zbus::Result<()>;
+
+        /// Same as `trigger_invoked`, along with a description of the client that invoked it
+        #[dbus_interface(signal, name = "trigger_invoked_by")]
+        async fn trigger_invoked_by(
+            ctx: &SignalContext<'_>,
+            schema: String,
+            trigger: String,
+            value: OwnedValue,
+            sender: String,
+        ) -> zbus::Result<()>;
     }
 
     #[dbus_interface(name = "org.glud.GludConfig.Property")]
@@ -140,34 +348,148 @@ mod interface {
             key_name: String,
         ) -> zbus::Result<()>;
 
+        /// Same as `property_changed`, along with the new and the previous value, a serial and a
+        /// description of the client whose call changed the property, see `describe_caller`.
+        /// Serials grow with every change, restarts of the daemon included, so a listener that
+        /// saw serial `n` can ignore anything up to it.
+        #[dbus_interface(signal, name = "property_changed_v2")]
+        async fn property_changed_v2(
+            ctx: &SignalContext<'_>,
+            schema_name: String,
+            key_name: String,
+            value: Nullable,
+            previous: Nullable,
+            serial: u64,
+            sender: String,
+        ) -> zbus::Result<()>;
+
+        /// Emitted when the `enabled_when` condition of a property flips
+        #[dbus_interface(signal, name = "enabled_changed")]
+        async fn enabled_changed(
+            ctx: &SignalContext<'_>,
+            schema_name: String,
+            key_name: String,
+            enabled: bool,
+        ) -> zbus::Result<()>;
+
         #[dbus_interface(name = "set")]
         async fn set(
             &self,
             #[zbus(signal_context)] signal_ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
             schema_name: String,
             key_name: String,
             set_value: Nullable,
-        ) -> zbus::fdo::Result<()> {
-            let mut schema = self.storage.get_schema(schema_name.clone()).await?;
-            let property = schema
-                .properties_mut()
-                .find(|p| p.name() == &key_name)
-                .ok_or(Into::<zbus::fdo::Error>::into(ZbusError::PropertyNotFound(
-                    &schema_name,
-                    &key_name,
-                )))?;
-            let value: gludconfig::value::Value = ::gludconfig::value::Value::new::<OwnedValue>(
-                set_value.into(),
-                property.signature(),
+        ) -> Result<(), DaemonError> {
+            authorize(
+                &self.policy,
+                signal_ctx.connection(),
+                &header,
+                &schema_name,
+                Access::Write,
             )
-            .map_err(|err| zbus::fdo::Error::Failed(format!("{}", err)))?;
+            .await?;
+            let _guard = self.storage.lock_schema(&schema_name).await;
+            self.write(&signal_ctx, &header, schema_name, key_name, set_value)
+                .await
+        }
 
-            property
-                .set_value(value)
-                .map_err(|err| zbus::fdo::Error::Failed(format!("{}", err)))?;
+        /// Sets a property only if it still holds `expected`, and fails with
+        /// `org.glud.GludConfig.Error.ValueChanged` otherwise, so that read-modify-write cycles of
+        /// concurrent clients do not overwrite each other
+        #[dbus_interface(name = "set_if")]
+        async fn set_if(
+            &self,
+            #[zbus(signal_context)] signal_ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+            key_name: String,
+            expected: Nullable,
+            set_value: Nullable,
+        ) -> Result<(), DaemonError> {
+            authorize(
+                &self.policy,
+                signal_ctx.connection(),
+                &header,
+                &schema_name,
+                Access::Write,
+            )
+            .await?;
+            let _guard = self.storage.lock_schema(&schema_name).await;
+            let schema = self.schema_for(&header, &schema_name).await?;
+            let current = schema.values().remove(&key_name).ok_or_else(|| {
+                DaemonError::from(ZbusError::PropertyNotFound(&schema_name, &key_name))
+            })?;
+            if current != expected {
+                return Err(DaemonError::ValueChanged(format!(
+                    "{} in schema {} does not hold the expected value anymore",
+                    key_name, schema_name
+                )));
+            }
+            self.write(&signal_ctx, &header, schema_name, key_name, set_value)
+                .await
+        }
+
+        /// Sets several properties of a schema at once. Either every value is written or, if one
+        /// of them fails, none is.
+        #[dbus_interface(name = "set_many")]
+        async fn set_many(
+            &self,
+            #[zbus(signal_context)] signal_ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+            values: Vec<(String, Nullable)>,
+        ) -> Result<(), DaemonError> {
+            authorize(
+                &self.policy,
+                signal_ctx.connection(),
+                &header,
+                &schema_name,
+                Access::Write,
+            )
+            .await?;
+            let _guard = self.storage.lock_schema(&schema_name).await;
+            let mut schema = self.storage.get_schema(schema_name.clone()).await?;
+            if let Some(mut session) = self.session(&header, &schema_name) {
+                for (key_name, _) in &values {
+                    warn_if_deprecated(&schema, key_name, signal_ctx.connection(), &header).await;
+                }
+                session
+                    .set_many(schema, values)
+                    .map_err(DaemonError::from)?;
+                self.stage(&header, &schema_name, session);
+                return Ok(());
+            }
+            let enabled = schema.enabled_states();
+            let before = schema.values();
+            let mut changed = vec![];
+            for (key_name, set_value) in values {
+                warn_if_deprecated(&schema, &key_name, signal_ctx.connection(), &header).await;
+                let key = schema
+                    .set(&key_name, set_value)
+                    .map_err(DaemonError::from)?;
+                if !changed.contains(&key) {
+                    changed.push(key);
+                }
+            }
+            for key in schema.recompute() {
+                if !changed.contains(&key) {
+                    changed.push(key);
+                }
+            }
 
             self.storage.update_schema(&schema).await?;
-            Self::property_changed(&signal_ctx, schema_name, key_name).await?;
+            let keys: Vec<String> = changed.iter().flat_map(|key| schema.keys_of(key)).collect();
+            emit_property_changed(
+                signal_ctx.connection(),
+                &header,
+                &self.storage,
+                &schema,
+                &before,
+                keys,
+            )
+            .await?;
+            emit_enabled_changed(signal_ctx.connection(), &schema, &enabled).await?;
             Ok(())
         }
 
@@ -175,69 +497,485 @@ mod interface {
         async fn reset(
             &self,
             #[zbus(signal_context)] ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
             schema_name: String,
             key_name: String,
-        ) -> zbus::fdo::Result<bool> {
+        ) -> Result<bool, DaemonError> {
+            authorize(
+                &self.policy,
+                ctx.connection(),
+                &header,
+                &schema_name,
+                Access::Write,
+            )
+            .await?;
+            let _guard = self.storage.lock_schema(&schema_name).await;
             let mut schema = self.storage.get_schema(schema_name.clone()).await?;
-            let property = schema
-                .properties_mut()
-                .find(|p| p.name() == &key_name)
-                .ok_or(Into::<zbus::fdo::Error>::into(ZbusError::PropertyNotFound(
-                    &schema_name,
-                    &key_name,
-                )))?;
+            warn_if_deprecated(&schema, &key_name, ctx.connection(), &header).await;
+            if let Some(mut session) = self.session(&header, &schema_name) {
+                let was_reset = session
+                    .reset(schema, &key_name)
+                    .map_err(DaemonError::from)?;
+                self.stage(&header, &schema_name, session);
+                return Ok(was_reset);
+            }
+            let enabled = schema.enabled_states();
+            let before = schema.values();
+            let property = schema.resolve_mut(&key_name).ok_or(DaemonError::from(
+                ZbusError::PropertyNotFound(&schema_name, &key_name),
+            ))?;
 
             let was_reset = property.reset();
+            let mut changed = vec![property.name().to_string()];
+            changed.extend(schema.recompute());
             self.storage.update_schema(&schema).await?;
             if was_reset {
-                Self::property_changed(&ctx, schema_name, key_name).await?
+                let keys: Vec<String> =
+                    changed.iter().flat_map(|key| schema.keys_of(key)).collect();
+                emit_property_changed(
+                    ctx.connection(),
+                    &header,
+                    &self.storage,
+                    &schema,
+                    &before,
+                    keys,
+                )
+                .await?;
+                emit_enabled_changed(ctx.connection(), &schema, &enabled).await?;
             };
             Ok(was_reset)
         }
 
+        /// The original description of a property, see `metadata_v2` for everything else
         #[dbus_interface(name = "metadata")]
         async fn metadata(
             &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+            key_name: String,
+        ) -> Result<PropertyInfoV0, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
+            let schema = self.schema_for(&header, &schema_name).await?;
+            warn_if_deprecated(&schema, &key_name, conn, &header).await;
+            schema
+                .property_info(&key_name, None)
+                .map(Into::into)
+                .map_err(DaemonError::from)
+        }
+
+        /// Full description of a property, with `about` and `long_about` translated to `locale`
+        /// where available. An empty `locale` leaves them untranslated.
+        #[dbus_interface(name = "metadata_v2")]
+        async fn metadata_v2(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
             schema_name: String,
             key_name: String,
-        ) -> zbus::fdo::Result<PropertyInfo> {
+            locale: String,
+        ) -> Result<PropertyInfo, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
+            let schema = self.schema_for(&header, &schema_name).await?;
+            warn_if_deprecated(&schema, &key_name, conn, &header).await;
+            let locale = Some(locale.as_str()).filter(|locale| !locale.is_empty());
+            schema
+                .property_info(&key_name, locale)
+                .map_err(DaemonError::from)
+        }
+
+        /// Current values of `keys`, all read from a single fetch of the schema
+        #[dbus_interface(name = "get_many")]
+        async fn get_many(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+            keys: Vec<String>,
+        ) -> Result<BTreeMap<String, Nullable>, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
+            let schema = self.schema_for(&header, &schema_name).await?;
+            let values = schema.values();
+            let mut result = BTreeMap::new();
+            for key in keys {
+                warn_if_deprecated(&schema, &key, conn, &header).await;
+                let value = values.get(&key).cloned().ok_or(DaemonError::from(
+                    ZbusError::PropertyNotFound(&schema_name, &key),
+                ))?;
+                result.insert(key, value);
+            }
+            Ok(result)
+        }
+
+        /// Current value of every property of a schema
+        #[dbus_interface(name = "get_all")]
+        async fn get_all(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> Result<BTreeMap<String, Nullable>, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
+            Ok(self.schema_for(&header, &schema_name).await?.values())
+        }
+
+        /// Starts delayed-apply mode for the caller on a schema: its `set`, `set_many` and
+        /// `reset` calls are validated but only staged, and its reads see the staged values,
+        /// until it calls `apply` or `revert`. Staged writes are dropped when it leaves the bus.
+        #[dbus_interface(name = "delay")]
+        async fn delay(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> Result<(), DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Write).await?;
+            // Fails with a proper error if the schema does not exist.
+            self.storage.get_schema(schema_name.clone()).await?;
+            let key = session_key(&header, &schema_name);
+            self.sessions.lock().unwrap().entry(key).or_default();
+            Ok(())
+        }
+
+        /// Writes everything the caller staged on a schema at once and leaves delayed-apply mode
+        #[dbus_interface(name = "apply")]
+        async fn apply(
+            &self,
+            #[zbus(signal_context)] signal_ctx: SignalContext<'_>,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> Result<(), DaemonError> {
+            authorize(
+                &self.policy,
+                signal_ctx.connection(),
+                &header,
+                &schema_name,
+                Access::Write,
+            )
+            .await?;
+            let _guard = self.storage.lock_schema(&schema_name).await;
+            let Some(session) = self.session(&header, &schema_name) else {
+                return Ok(());
+            };
             let mut schema = self.storage.get_schema(schema_name.clone()).await?;
+            let enabled = schema.enabled_states();
+            let before = schema.values();
+            // Validated again, the schema may have changed since the writes were staged.
+            let changed = session.apply(&mut schema).map_err(DaemonError::from)?;
 
-            let property = schema
-                .into_properties()
-                .find(|p| p.name() == &key_name)
-                .ok_or(Into::<zbus::fdo::Error>::into(ZbusError::PropertyNotFound(
-                    &schema_name,
-                    &key_name,
-                )))?;
+            self.storage.update_schema(&schema).await?;
+            let key = session_key(&header, &schema_name);
+            self.sessions.lock().unwrap().remove(&key);
+            let keys: Vec<String> = changed.iter().flat_map(|key| schema.keys_of(key)).collect();
+            emit_property_changed(
+                signal_ctx.connection(),
+                &header,
+                &self.storage,
+                &schema,
+                &before,
+                keys,
+            )
+            .await?;
+            emit_enabled_changed(signal_ctx.connection(), &schema, &enabled).await?;
+            Ok(())
+        }
+
+        /// Drops everything the caller staged on a schema and leaves delayed-apply mode. Returns
+        /// whether there was anything staged.
+        #[dbus_interface(name = "revert")]
+        async fn revert(
+            &self,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> bool {
+            let key = session_key(&header, &schema_name);
+            let session = self.sessions.lock().unwrap().remove(&key);
+            session.is_some_and(|session| !session.is_empty())
+        }
+
+        /// Whether the caller staged writes on a schema that are not applied yet
+        #[dbus_interface(name = "has_unapplied")]
+        async fn has_unapplied(
+            &self,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+        ) -> bool {
+            self.session(&header, &schema_name)
+                .is_some_and(|session| !session.is_empty())
+        }
 
-            Ok(property.into())
+        /// Same as `metadata_v2` with a locale, kept for clients written against it
+        #[dbus_interface(name = "metadata_localized")]
+        async fn metadata_localized(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            #[zbus(header)] header: MessageHeader<'_>,
+            schema_name: String,
+            key_name: String,
+            locale: String,
+        ) -> Result<PropertyInfo, DaemonError> {
+            authorize(&self.policy, conn, &header, &schema_name, Access::Read).await?;
+            let schema = self.schema_for(&header, &schema_name).await?;
+            warn_if_deprecated(&schema, &key_name, conn, &header).await;
+            schema
+                .property_info(&key_name, Some(&locale))
+                .map_err(DaemonError::from)
         }
     }
 
-    #[derive(serde::Serialize, serde::Deserialize, zvariant::Type, zvariant::Value)]
-    struct PropertyInfo {
-        writable: bool,
-        name: String,
-        description: String,
-        summary: String,
-        show_in_setitngs: bool,
-        signature: OwnedSignature,
-        current: Nullable,
+    impl PropertyInterface {
+        /// The delayed-apply session the sender of `header` has open on `schema_name`, if any
+        fn session(&self, header: &MessageHeader<'_>, schema_name: &str) -> Option<Session> {
+            let key = session_key(header, schema_name);
+            self.sessions.lock().unwrap().get(&key).cloned()
+        }
+
+        fn stage(&self, header: &MessageHeader<'_>, schema_name: &str, session: Session) {
+            let key = session_key(header, schema_name);
+            self.sessions.lock().unwrap().insert(key, session);
+        }
+
+        /// `set`, with the lock of the schema already held
+        async fn write(
+            &self,
+            signal_ctx: &SignalContext<'_>,
+            header: &MessageHeader<'_>,
+            schema_name: String,
+            key_name: String,
+            set_value: Nullable,
+        ) -> Result<(), DaemonError> {
+            let mut schema = self.storage.get_schema(schema_name.clone()).await?;
+            warn_if_deprecated(&schema, &key_name, signal_ctx.connection(), header).await;
+            if let Some(mut session) = self.session(header, &schema_name) {
+                session
+                    .set(schema, &key_name, set_value)
+                    .map_err(DaemonError::from)?;
+                self.stage(header, &schema_name, session);
+                return Ok(());
+            }
+            let enabled = schema.enabled_states();
+            let before = schema.values();
+            let mut changed = vec![schema
+                .set(&key_name, set_value)
+                .map_err(DaemonError::from)?];
+            changed.extend(schema.recompute());
+
+            self.storage.update_schema(&schema).await?;
+            let keys: Vec<String> = changed.iter().flat_map(|key| schema.keys_of(key)).collect();
+            emit_property_changed(
+                signal_ctx.connection(),
+                header,
+                &self.storage,
+                &schema,
+                &before,
+                keys,
+            )
+            .await?;
+            emit_enabled_changed(signal_ctx.connection(), &schema, &enabled).await?;
+            Ok(())
+        }
+
+        /// The schema as the sender of `header` sees it, with the writes it staged applied as far
+        /// as they still apply, see `Session::preview`
+        async fn schema_for(
+            &self,
+            header: &MessageHeader<'_>,
+            schema_name: &str,
+        ) -> Result<Schema, DaemonError> {
+            let mut schema = self.storage.get_schema(schema_name.to_string()).await?;
+            if let Some(session) = self.session(header, schema_name) {
+                session.preview(&mut schema);
+            }
+            Ok(schema)
+        }
     }
 
-    impl From<Property> for PropertyInfo {
-        fn from(value: Property) -> Self {
-            PropertyInfo {
-                name: value.name().to_string(),
-                writable: value.is_writable(),
-                description: value.long_about().to_string(),
-                summary: value.about().to_string(),
-                show_in_setitngs: value.show_in_settings(),
-                signature: value.signature().into(),
-                current: <Property as Into<gludconfig::value::Value>>::into(value).into(),
+    fn session_key(header: &MessageHeader<'_>, schema_name: &str) -> (String, String) {
+        let sender = header.sender().ok().flatten();
+        (
+            sender.map(|sender| sender.to_string()).unwrap_or_default(),
+            schema_name.to_string(),
+        )
+    }
+
+    /// Emits `property_changed` and `property_changed_v2` for every key in `keys`, `before` being
+    /// the values of the schema from before the change
+    async fn emit_property_changed(
+        conn: &zbus::Connection,
+        header: &MessageHeader<'_>,
+        storage: &Storage,
+        schema: &Schema,
+        before: &BTreeMap<String, Nullable>,
+        keys: Vec<String>,
+    ) -> Result<(), DaemonError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut serial = storage.next_serials(keys.len() as u64).await?;
+        let sender = describe_caller(conn, header).await;
+        let values = schema.values();
+        let interface = <PropertyInterface as ::zbus::Interface>::name();
+        for key in keys {
+            let path = "/org/glud/gludconfig/property";
+            conn.emit_signal(
+                Option::<&BusName<'static>>::None,
+                path,
+                interface.clone(),
+                "property_changed",
+                &(schema.name(), &key),
+            )
+            .await?;
+            let value = values.get(&key).cloned().unwrap_or_default();
+            let previous = before.get(&key).cloned().unwrap_or_default();
+            conn.emit_signal(
+                Option::<&BusName<'static>>::None,
+                path,
+                interface.clone(),
+                "property_changed_v2",
+                &(schema.name(), &key, value, previous, serial, &sender),
+            )
+            .await?;
+            serial += 1;
+        }
+        Ok(())
+    }
+
+    /// Emits `enabled_changed` for every property whose enabled state differs from `before`
+    async fn emit_enabled_changed(
+        conn: &zbus::Connection,
+        schema: &Schema,
+        before: &BTreeMap<String, bool>,
+    ) -> zbus::Result<()> {
+        for (key, enabled) in schema.enabled_states() {
+            if before.get(&key) == Some(&enabled) {
+                continue;
+            }
+            for key in schema.keys_of(&key) {
+                conn.emit_signal(
+                    Option::<&BusName<'static>>::None,
+                    "/org/glud/gludconfig/property",
+                    <PropertyInterface as ::zbus::Interface>::name(),
+                    "enabled_changed",
+                    &(schema.name(), key, enabled),
+                )
+                .await?;
             }
         }
+        Ok(())
+    }
+
+    /// Logs a warning naming the caller if `key_name` refers to a deprecated property
+    async fn warn_if_deprecated(
+        schema: &Schema,
+        key_name: &str,
+        conn: &zbus::Connection,
+        header: &MessageHeader<'_>,
+    ) {
+        let Some(property) = schema.properties().find(|p| p.name() == key_name) else {
+            return;
+        };
+        if !property.is_deprecated() {
+            return;
+        }
+
+        let forwarded = schema
+            .resolve(key_name)
+            .filter(|resolved| resolved.name() != key_name)
+            .map(|resolved| format!(", forwarded to `{}`", resolved.name()))
+            .unwrap_or_default();
+
+        eprintln!(
+            "warning: {} accessed deprecated property `{}` of schema `{}`{}: {}",
+            describe_caller(conn, header).await,
+            key_name,
+            schema.name(),
+            forwarded,
+            property.deprecation()
+        );
+    }
+
+    /// Describes the sender of a message as `<unique name> (pid <pid>, <executable>)`, as far as it can be found out
+    async fn describe_caller(conn: &zbus::Connection, header: &MessageHeader<'_>) -> String {
+        let Some(sender) = header.sender().ok().flatten() else {
+            return "unknown caller".to_string();
+        };
+
+        match caller_credentials(conn, header).await {
+            Caller {
+                pid: Some(pid),
+                executable,
+                ..
+            } => format!(
+                "{} (pid {}, {})",
+                sender,
+                pid,
+                executable.as_deref().unwrap_or("unknown executable")
+            ),
+            _ => sender.to_string(),
+        }
+    }
+
+    /// Looks up the sender of a message with `org.freedesktop.DBus.GetConnectionCredentials`
+    async fn caller_credentials(conn: &zbus::Connection, header: &MessageHeader<'_>) -> Caller {
+        let Some(sender) = header.sender().ok().flatten().map(|s| s.to_owned()) else {
+            return Caller::default();
+        };
+
+        let credentials = match zbus::fdo::DBusProxy::new(conn).await {
+            Ok(proxy) => proxy.get_connection_credentials(sender.into()).await.ok(),
+            Err(_) => None,
+        };
+        let Some(credentials) = credentials else {
+            return Caller::default();
+        };
+        let pid = credentials.process_id();
+        Caller {
+            uid: credentials.unix_user_id(),
+            pid,
+            executable: pid
+                .and_then(|pid| std::fs::read_link(format!("/proc/{}/exe", pid)).ok())
+                .map(|path| path.display().to_string()),
+            app_id: pid.and_then(flatpak_app_id),
+        }
+    }
+
+    /// The app id of a process running in a Flatpak sandbox, from its `/.flatpak-info`
+    fn flatpak_app_id(pid: u32) -> Option<String> {
+        let info = std::fs::read_to_string(format!("/proc/{}/root/.flatpak-info", pid)).ok()?;
+        let keyfile = KeyFile::parse(&info).ok()?;
+        let group = keyfile
+            .groups
+            .into_iter()
+            .find(|group| group.name == "Application")?;
+        group
+            .entries
+            .into_iter()
+            .find(|entry| entry.key == "name")
+            .map(|entry| entry.value)
+    }
+
+    /// Fails with `PermissionDenied` unless the policy gives the sender of `header` at least
+    /// `access` to `schema_name`
+    async fn authorize(
+        policy: &Policy,
+        conn: &zbus::Connection,
+        header: &MessageHeader<'_>,
+        schema_name: &str,
+        access: Access,
+    ) -> Result<(), DaemonError> {
+        if policy.allows_everyone(access) {
+            return Ok(());
+        }
+        let caller = caller_credentials(conn, header).await;
+        if policy.access(&caller, schema_name) >= access {
+            return Ok(());
+        }
+        Err(DaemonError::PermissionDenied(format!(
+            "{} needs `{}` access to schema {}",
+            describe_caller(conn, header).await,
+            access.as_str(),
+            schema_name
+        )))
     }
 
     #[derive(serde::Serialize, serde::Deserialize, zvariant::Type, zvariant::Value)]
@@ -286,34 +1024,70 @@ mod interface {
 async fn main() -> anyhow::Result<()> {
     use std::sync::Arc;
 
+    use futures_util::StreamExt;
+    use gludconfig::policy::Policy;
+
     use interface::TriggerInterface;
 
     use crate::interface::{PropertyInterface, SchemaInterface};
 
     let storage = Arc::new(gludconfig::storage::Storage::new().await?);
+    let sessions = interface::Sessions::default();
+    let policy = Arc::new(Policy::load(Policy::path()?)?);
+    // Waits for offline tools that are still writing, then keeps them out while running.
+    let _lock = storage.lock(true)?;
     let connection = zbus::ConnectionBuilder::session()?
         .name("org.glud.GludConfig")?
         .serve_at(
             "/org/glud/gludconfig/property",
             PropertyInterface {
                 storage: storage.clone(),
+                sessions: sessions.clone(),
+                policy: policy.clone(),
             },
         )?
         .serve_at(
             "/org/glud/gludconfig/trigger",
             TriggerInterface {
                 storage: storage.clone(),
+                policy: policy.clone(),
             },
         )?
         .serve_at(
             "/org/glud/gludconfig/schema",
             SchemaInterface {
                 storage: storage.clone(),
+                policy,
             },
         )?
         .build()
         .await?;
 
+    // Writes staged by clients that left the bus are never applied.
+    let mut owner_changes = zbus::fdo::DBusProxy::new(&connection)
+        .await?
+        .receive_name_owner_changed()
+        .await?;
+    while let Some(change) = owner_changes.next().await {
+        let args = match change.args() {
+            Ok(args) => args,
+            Err(err) => {
+                eprintln!(
+                    "warning: ignoring an undecodable NameOwnerChanged signal: {}",
+                    err
+                );
+                continue;
+            }
+        };
+        if args.new_owner().is_none() {
+            let name = args.name().to_string();
+            sessions
+                .lock()
+                .unwrap()
+                .retain(|(sender, _), _| *sender != name);
+        }
+    }
+
     std::future::pending::<()>().await;
     Ok(())
 }
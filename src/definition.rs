@@ -0,0 +1,288 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
+
+use zvariant::Signature;
+
+use crate::{
+    error::DefinitionError,
+    property::{Property, WidgetHint},
+    schema::Schema,
+    trigger::Trigger,
+    value::Value,
+};
+
+/// The format of a textual schema definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Picks the format by file extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = DefinitionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            other => Err(DefinitionError::UnknownFormat(other.to_string())),
+        }
+    }
+}
+
+/// A schema written in TOML or JSON, as an alternative to the `Schema` derive.
+///
+/// Defaults and choices are written as plain values and converted with `Value::from_json`
+/// against the property's signature:
+///
+/// ```toml
+/// name = "org.foo.display"
+/// version = 1
+///
+/// [[properties]]
+/// name = "scale"
+/// signature = "d"
+/// default = 1.0
+/// choices = [1.0, 1.5, 2.0]
+///
+/// [[triggers]]
+/// name = "reload"
+/// signature = "s"
+/// ```
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaDefinition {
+    name: String,
+    version: u32,
+    #[serde(default)]
+    properties: Vec<PropertyDefinition>,
+    #[serde(default)]
+    triggers: Vec<TriggerDefinition>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PropertyDefinition {
+    name: String,
+    signature: String,
+    #[serde(default)]
+    default: serde_json::Value,
+    #[serde(default)]
+    choices: Vec<serde_json::Value>,
+    writable: Option<bool>,
+    about: Option<String>,
+    long_about: Option<String>,
+    show_in_settings: Option<bool>,
+    group: Option<String>,
+    order: Option<i32>,
+    widget: Option<String>,
+    advanced: Option<bool>,
+    deprecated: Option<String>,
+    replaced_by: Option<String>,
+    enabled_when: Option<String>,
+    reject_when_disabled: Option<bool>,
+    computed: Option<String>,
+    #[serde(default)]
+    about_l10n: BTreeMap<String, String>,
+    #[serde(default)]
+    long_about_l10n: BTreeMap<String, String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerDefinition {
+    name: String,
+    signature: String,
+}
+
+impl SchemaDefinition {
+    pub fn parse(text: &str, format: Format) -> anyhow::Result<Self> {
+        let json = match format {
+            Format::Json => serde_json::from_str(text)?,
+            Format::Toml => {
+                let document = text
+                    .parse::<toml_edit::Document>()
+                    .map_err(|err| DefinitionError::InvalidToml(err.to_string()))?;
+                toml_table_to_json(document.as_table().iter())?
+            }
+        };
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Reads a definition from a file, picking the format by its extension
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let format = Format::from_path(path)
+            .ok_or(DefinitionError::UnknownFormat(path.display().to_string()))?;
+        Self::parse(&std::fs::read_to_string(path)?, format)
+            .map_err(|err| err.context(format!("While loading {}", path.display())))
+    }
+
+    pub fn into_schema(self) -> anyhow::Result<Schema> {
+        let mut names = HashSet::new();
+        if let Some(property) = self.properties.iter().find(|p| !names.insert(&p.name)) {
+            return Err(DefinitionError::DuplicateProperty(property.name.clone()).into());
+        }
+        let mut names = HashSet::new();
+        if let Some(trigger) = self.triggers.iter().find(|t| !names.insert(&t.name)) {
+            return Err(DefinitionError::DuplicateTrigger(trigger.name.clone()).into());
+        }
+
+        let properties = self
+            .properties
+            .into_iter()
+            .map(|property| {
+                let name = property.name.clone();
+                property
+                    .into_property()
+                    .map_err(|err| err.context(format!("In property {}", name)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let triggers = self
+            .triggers
+            .into_iter()
+            .map(|trigger| {
+                let signature = parse_signature(trigger.signature)
+                    .map_err(|err| err.context(format!("In trigger {}", trigger.name)))?;
+                Ok(Trigger::new(trigger.name, signature))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Schema::builder()
+            .name(self.name)
+            .version(self.version)
+            .properties(properties)
+            .triggers(triggers)
+            .build()
+    }
+}
+
+impl PropertyDefinition {
+    fn into_property(self) -> anyhow::Result<Property> {
+        let signature = parse_signature(self.signature)?;
+        let value = |json: &serde_json::Value, what: &str| {
+            Value::from_json(json, &signature)
+                .map_err(|err| err.context(format!("While reading {}", what)))
+        };
+
+        let mut builder = Property::builder()
+            .name(self.name)
+            .signature(signature.clone())
+            .default(value(&self.default, "the default")?)
+            .choices(
+                self.choices
+                    .iter()
+                    .map(|choice| value(choice, "a choice"))
+                    .collect::<anyhow::Result<_>>()?,
+            );
+
+        macro_rules! set {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    builder = builder.$field(value);
+                })*
+            };
+        }
+        set!(
+            writable,
+            about,
+            long_about,
+            show_in_settings,
+            group,
+            order,
+            advanced,
+            deprecated,
+            replaced_by,
+            enabled_when,
+            reject_when_disabled,
+            computed
+        );
+
+        if let Some(widget) = self.widget {
+            builder = builder.widget(widget.parse::<WidgetHint>()?);
+        }
+        for (locale, about) in self.about_l10n {
+            builder = builder.about_translation(locale, about);
+        }
+        for (locale, long_about) in self.long_about_l10n {
+            builder = builder.long_about_translation(locale, long_about);
+        }
+        builder.build()
+    }
+}
+
+/// Maybe types (`m`) are rejected, they can't be stored since storage encodes values as D-Bus
+fn parse_signature(signature: String) -> anyhow::Result<Signature<'static>> {
+    if signature.contains('m') {
+        return Err(DefinitionError::InvalidSignature(signature).into());
+    }
+    Signature::try_from(signature.clone())
+        .map_err(|_| DefinitionError::InvalidSignature(signature).into())
+}
+
+pub(crate) fn toml_table_to_json<'a>(
+    entries: impl Iterator<Item = (&'a str, &'a toml_edit::Item)>,
+) -> Result<serde_json::Value, DefinitionError> {
+    let mut object = serde_json::Map::new();
+    for (key, item) in entries {
+        let value = match item {
+            toml_edit::Item::None => continue,
+            toml_edit::Item::Value(value) => toml_value_to_json(value)?,
+            toml_edit::Item::Table(table) => toml_table_to_json(table.iter())?,
+            toml_edit::Item::ArrayOfTables(tables) => serde_json::Value::Array(
+                tables
+                    .iter()
+                    .map(|table| toml_table_to_json(table.iter()))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        object.insert(key.to_string(), value);
+    }
+    Ok(serde_json::Value::Object(object))
+}
+
+fn toml_value_to_json(value: &toml_edit::Value) -> Result<serde_json::Value, DefinitionError> {
+    use toml_edit::Value as T;
+
+    Ok(match value {
+        T::String(string) => serde_json::Value::from(string.value().as_str()),
+        T::Integer(integer) => serde_json::Value::from(*integer.value()),
+        T::Float(float) => serde_json::Value::from(*float.value()),
+        T::Boolean(boolean) => serde_json::Value::from(*boolean.value()),
+        T::Datetime(datetime) => {
+            return Err(DefinitionError::UnsupportedToml(
+                datetime.value().to_string(),
+            ))
+        }
+        T::Array(array) => serde_json::Value::Array(
+            array
+                .iter()
+                .map(toml_value_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        T::InlineTable(table) => {
+            let mut object = serde_json::Map::new();
+            for (key, value) in table.iter() {
+                object.insert(key.to_string(), toml_value_to_json(value)?);
+            }
+            serde_json::Value::Object(object)
+        }
+    })
+}
@@ -2,6 +2,18 @@
 pub enum ValueError {
     #[error("The value's signature, and Signature passed to `Value::new` dont match")]
     SignatureNotMatched,
+    #[error("{1} at position {0}")]
+    Parse(usize, String),
+    #[error("{1} at {0}")]
+    Json(String, String),
+    #[error("Expected a value, found `nothing`")]
+    Null,
+}
+
+impl ValueError {
+    pub(crate) fn parse(position: usize, message: impl Into<String>) -> Self {
+        Self::Parse(position, message.into())
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -15,10 +27,97 @@ pub enum ZbusError<'a> {
 }
 
 
+/// The errors the daemon replies with, named `org.glud.GludConfig.Error.<Variant>` so that
+/// clients can tell them apart without parsing the message, which every variant carries
+#[cfg(feature = "dbus")]
+#[derive(zbus::DBusError, Debug)]
+#[dbus_error(prefix = "org.glud.GludConfig.Error")]
+pub enum DaemonError {
+    #[dbus_error(zbus_error)]
+    ZBus(zbus::Error),
+    SchemaNotFound(String),
+    PropertyNotFound(String),
+    TriggerNotFound(String),
+    NotWritable(String),
+    NotInChoices(String),
+    /// The property is disabled by its `enabled_when` condition
+    Disabled(String),
+    SignatureMismatch(String),
+    InvalidValue(String),
+    InvalidSchema(String),
+    /// `set_if` found another value than the expected one
+    ValueChanged(String),
+    /// The policy does not allow the caller to do this, see `policy::Policy`
+    PermissionDenied(String),
+    Storage(String),
+    Failed(String),
+}
+
+#[cfg(feature = "dbus")]
+pub type DaemonResult<T> = Result<T, DaemonError>;
+
 #[cfg(feature = "dbus")]
-impl<'a> Into<zbus::fdo::Error> for ZbusError<'a> {
-    fn into(self) -> zbus::fdo::Error {
-        zbus::fdo::Error::Failed(format!("{}", self))
+impl From<anyhow::Error> for DaemonError {
+    /// Picks the variant by the first cause in the chain that is recognized
+    fn from(err: anyhow::Error) -> Self {
+        let message = format!("{:#}", err);
+        let variant = err.chain().find_map(|cause| -> Option<fn(String) -> Self> {
+            if let Some(err) = cause.downcast_ref::<StorageError>() {
+                return Some(match err {
+                    StorageError::SchemaNotFound(_) => Self::SchemaNotFound,
+                    _ => Self::Storage,
+                });
+            }
+            if let Some(err) = cause.downcast_ref::<SchemaError>() {
+                return Some(match err {
+                    SchemaError::PropertyNotFound(..) => Self::PropertyNotFound,
+                    SchemaError::TriggerNotFound(..) => Self::TriggerNotFound,
+                    _ => Self::InvalidSchema,
+                });
+            }
+            if let Some(err) = cause.downcast_ref::<PropertyError>() {
+                return Some(match err {
+                    PropertyError::NotFoundInChoices => Self::NotInChoices,
+                    PropertyError::InvalidSignature => Self::SignatureMismatch,
+                    PropertyError::NotWritable => Self::NotWritable,
+                    PropertyError::Disabled => Self::Disabled,
+                    PropertyError::UnknownWidget => Self::InvalidSchema,
+                });
+            }
+            if let Some(err) = cause.downcast_ref::<ValueError>() {
+                return Some(match err {
+                    ValueError::SignatureNotMatched => Self::SignatureMismatch,
+                    _ => Self::InvalidValue,
+                });
+            }
+            if cause.is::<ExprError>() {
+                return Some(Self::InvalidValue);
+            }
+            if cause.is::<DefinitionError>() || cause.is::<BuilderError>() {
+                return Some(Self::InvalidSchema);
+            }
+            None
+        });
+        variant.unwrap_or(Self::Failed)(message)
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl From<zbus::fdo::Error> for DaemonError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        Self::ZBus(err.into())
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl<'a> From<ZbusError<'a>> for DaemonError {
+    fn from(err: ZbusError<'a>) -> Self {
+        let message = err.to_string();
+        match err {
+            ZbusError::SchemaNotFound(_) => Self::SchemaNotFound(message),
+            ZbusError::PropertyNotFound(..) => Self::PropertyNotFound(message),
+            ZbusError::TriggerNotFound(..) => Self::TriggerNotFound(message),
+        }
     }
 }
 
@@ -26,6 +125,12 @@ impl<'a> Into<zbus::fdo::Error> for ZbusError<'a> {
 pub enum StorageError {
     #[error("No home directory found for user {0}")]
     NoHomeFound(&'static str),
+    #[error("Schema with name {0} not found")]
+    SchemaNotFound(String),
+    #[error("{0} is locked by another process, most likely the gludconfig daemon")]
+    Locked(String),
+    #[error("Stored schema has format version {0}, which this version of gludconfig cannot read")]
+    UnknownFormat(u32),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -44,6 +149,66 @@ pub enum PropertyError {
     InvalidSignature,
     #[error("Property cannot be writed to, as its `writable` field is set to false")]
     NotWritable,
+    #[error("Property cannot be written to while it is disabled by its `enabled_when` condition")]
+    Disabled,
+    #[error("Unknown widget hint, expected one of: auto, switch, slider, file_chooser, color")]
+    UnknownWidget,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("Property {0} is replaced by {1}, which does not exist in the schema")]
+    ReplacementNotFound(String, String),
+    #[error("Property {0} is replaced by {1}, but their signatures differ")]
+    ReplacementSignatureMismatch(String, String),
+    #[error("The replacements of property {0} form a cycle")]
+    ReplacementCycle(String),
+    #[error("The `enabled_when` condition of property {0} references unknown property {1}")]
+    UnknownDependency(String, String),
+    #[error("The `computed` expression of property {0} references unknown property {1}")]
+    UnknownInput(String, String),
+    #[error("Computed property {0} depends on itself")]
+    ComputedCycle(String),
+    #[error("Property not found in schema {0} with name {1}")]
+    PropertyNotFound(String, String),
+    #[error("Trigger not found in schema {0} with name {1}")]
+    TriggerNotFound(String, String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExprError {
+    #[error("{1} at position {0}")]
+    Parse(usize, String),
+    #[error("Unknown property `{0}` referenced in expression")]
+    UnknownProperty(String),
+    #[error("Type error: {0}")]
+    Type(String),
+    #[error("Values of signature `{0}` cannot be used in expressions")]
+    UnsupportedType(String),
+    #[error("Cannot convert {0} to a value of signature `{1}`")]
+    Conversion(String, String),
+    #[error("Integer overflow while evaluating expression")]
+    Overflow,
+    #[error("Division by zero while evaluating expression")]
+    DivisionByZero,
+}
+
+impl ExprError {
+    pub(crate) fn parse(position: usize, message: impl Into<String>) -> Self {
+        Self::Parse(position, message.into())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocaleError {
+    #[error("Unknown catalog format, expected a `.po`, `.mo` or `.ftl` file")]
+    UnknownFormat,
+    #[error("Malformed `.mo` file")]
+    InvalidMo,
+    #[error("Malformed `.po` file at line {0}: {1}")]
+    InvalidPo(usize, &'static str),
+    #[error("Malformed `.ftl` file at line {0}: {1}")]
+    InvalidFtl(usize, &'static str),
 }
 
 impl BuilderError {
@@ -51,3 +216,37 @@ impl BuilderError {
         anyhow::Error::new(Self::UnwrapFailed(value, builder)).context(ctx)
     }
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum DefinitionError {
+    #[error("Unknown schema definition format `{0}`, expected `toml` or `json`")]
+    UnknownFormat(String),
+    #[error("Invalid TOML: {0}")]
+    InvalidToml(String),
+    #[error("TOML dates and times are not supported, found `{0}`")]
+    UnsupportedToml(String),
+    #[error("Invalid signature `{0}`")]
+    InvalidSignature(String),
+    #[error("Property `{0}` is defined more than once")]
+    DuplicateProperty(String),
+    #[error("Trigger `{0}` is defined more than once")]
+    DuplicateTrigger(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyFileError {
+    #[error("Line {0}: {1}")]
+    Syntax(usize, &'static str),
+    #[error("Line {0}: group `{1}` appears twice")]
+    DuplicateGroup(usize, String),
+    #[error("Line {0}: key `{1}` appears twice in its group")]
+    DuplicateKey(usize, String),
+    #[error("Line {0}: unknown schema `{1}`")]
+    UnknownSchema(usize, String),
+    #[error("Line {0}: unknown key `{1}` in schema {2}")]
+    UnknownKey(usize, String, String),
+    #[error("Line {0}: key `{1}` is not writable")]
+    NotWritable(usize, String),
+    #[error("Line {0}: invalid value for `{1}`: {2}")]
+    InvalidValue(usize, String, String),
+}
@@ -0,0 +1,652 @@
+//! A small expression language used by schemas to describe conditions and computed values.
+//!
+//! Expressions can reference other properties of the same schema by name and support
+//! literals (`true`, `1`, `0.5`, `'text'`), arithmetic (`+ - * / %`), comparisons
+//! (`== != < <= > >=`), boolean logic (`&& || !`) and the functions `min`, `max`, `abs`,
+//! `round`, `floor`, `ceil` and `if(condition, then, else)`. There are no loops or side effects.
+
+use std::fmt::Display;
+
+use zvariant::{OwnedValue, Signature};
+
+use crate::error::ExprError;
+
+/// A value an expression evaluates to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scalar::Null => write!(f, "null"),
+            Scalar::Bool(value) => write!(f, "{}", value),
+            Scalar::Int(value) => write!(f, "{}", value),
+            Scalar::Float(value) => write!(f, "{}", value),
+            Scalar::Str(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl Scalar {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Scalar::Null => "null",
+            Scalar::Bool(_) => "bool",
+            Scalar::Int(_) => "int",
+            Scalar::Float(_) => "float",
+            Scalar::Str(_) => "string",
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Scalar::Int(value) => Some(*value as f64),
+            Scalar::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Converts a property value, `None` being a null property
+    pub fn from_value(value: Option<&OwnedValue>) -> Result<Self, ExprError> {
+        let Some(value) = value else {
+            return Ok(Scalar::Null);
+        };
+        Ok(match &**value {
+            zvariant::Value::Bool(v) => Scalar::Bool(*v),
+            zvariant::Value::U8(v) => Scalar::Int(*v as i64),
+            zvariant::Value::I16(v) => Scalar::Int(*v as i64),
+            zvariant::Value::U16(v) => Scalar::Int(*v as i64),
+            zvariant::Value::I32(v) => Scalar::Int(*v as i64),
+            zvariant::Value::U32(v) => Scalar::Int(*v as i64),
+            zvariant::Value::I64(v) => Scalar::Int(*v),
+            zvariant::Value::U64(v) => match i64::try_from(*v) {
+                Ok(v) => Scalar::Int(v),
+                Err(_) => Scalar::Float(*v as f64),
+            },
+            zvariant::Value::F64(v) => Scalar::Float(*v),
+            zvariant::Value::Str(v) => Scalar::Str(v.to_string()),
+            zvariant::Value::ObjectPath(v) => Scalar::Str(v.to_string()),
+            zvariant::Value::Signature(v) => Scalar::Str(v.to_string()),
+            other => {
+                return Err(ExprError::UnsupportedType(
+                    other.value_signature().to_string(),
+                ))
+            }
+        })
+    }
+
+    /// Converts the scalar into a value of signature `sig`, `None` being null
+    pub fn into_value(self, sig: &Signature<'_>) -> Result<Option<OwnedValue>, ExprError> {
+        fn int<T: TryFrom<i64>>(value: &Scalar) -> Option<T> {
+            match value {
+                Scalar::Int(v) => T::try_from(*v).ok(),
+                Scalar::Float(v) if v.fract() == 0.0 => T::try_from(*v as i64).ok(),
+                _ => None,
+            }
+        }
+
+        if self == Scalar::Null {
+            return Ok(None);
+        }
+
+        let value: Option<zvariant::Value<'static>> = match sig.as_str() {
+            "b" => match self {
+                Scalar::Bool(v) => Some(v.into()),
+                _ => None,
+            },
+            "y" => int::<u8>(&self).map(Into::into),
+            "n" => int::<i16>(&self).map(Into::into),
+            "q" => int::<u16>(&self).map(Into::into),
+            "i" => int::<i32>(&self).map(Into::into),
+            "u" => int::<u32>(&self).map(Into::into),
+            "x" => int::<i64>(&self).map(Into::into),
+            "t" => int::<u64>(&self).map(Into::into),
+            "d" => self.as_f64().map(Into::into),
+            "s" => match &self {
+                Scalar::Str(v) => Some(v.clone().into()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        value
+            .map(|value| Some(value.into()))
+            .ok_or(ExprError::Conversion(self.to_string(), sig.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Scalar),
+    Property(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+const OPERATORS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%",
+];
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' || c == ',' {
+            chars.next();
+            tokens.push((
+                pos,
+                match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                },
+            ));
+        } else if c.is_ascii_digit() {
+            let mut end = pos;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.' || c == '_') {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+            let number = text[pos..end].replace('_', "");
+            let token = match number.contains('.') {
+                true => number.parse().map(Token::Float).ok(),
+                false => number.parse().map(Token::Int).ok(),
+            };
+            tokens.push((
+                pos,
+                token.ok_or(ExprError::parse(
+                    pos,
+                    format!("invalid number `{}`", number),
+                ))?,
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = pos;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((pos, Token::Ident(text[pos..end].to_string())));
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(ExprError::parse(pos, "unterminated string")),
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => string.push(escaped),
+                        None => return Err(ExprError::parse(pos, "unterminated string")),
+                    },
+                    Some((_, quote)) if quote == c => break,
+                    Some((_, other)) => string.push(other),
+                }
+            }
+            tokens.push((pos, Token::Str(string)));
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| text[pos..].starts_with(*op))
+                .ok_or(ExprError::parse(
+                    pos,
+                    format!("unexpected character `{}`", c),
+                ))?;
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push((pos, Token::Op(op)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// How deeply parentheses, calls and unary operators may nest, which also bounds the recursion of
+/// `Expr::eval`
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    idx: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.idx)
+            .map(|(pos, _)| *pos)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.idx).map(|(_, token)| token.clone());
+        self.idx += 1;
+        token
+    }
+
+    fn eat_op(&mut self, ops: &[(&str, BinaryOp)]) -> Option<BinaryOp> {
+        let Some(Token::Op(op)) = self.peek() else {
+            return None;
+        };
+        let found = ops.iter().find(|(text, _)| text == op).map(|(_, op)| *op);
+        if found.is_some() {
+            self.idx += 1;
+        }
+        found
+    }
+
+    /// Enters one more level of nesting, failing past `MAX_DEPTH`
+    fn descend(&mut self) -> Result<(), ExprError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ExprError::parse(
+                self.position(),
+                format!("expression nested deeper than {} levels", MAX_DEPTH),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinaryOp)],
+        next: fn(&mut Self) -> Result<Expr, ExprError>,
+    ) -> Result<Expr, ExprError> {
+        let depth = self.depth;
+        let mut lhs = next(self)?;
+        // Every operator in a chain nests the expression so far one level deeper
+        while let Some(op) = self.eat_op(ops) {
+            self.descend()?;
+            let rhs = next(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        self.binary(&[("&&", BinaryOp::And)], Self::equality)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ExprError> {
+        self.binary(
+            &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+            Self::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExprError> {
+        self.binary(
+            &[
+                ("<", BinaryOp::Lt),
+                ("<=", BinaryOp::Le),
+                (">", BinaryOp::Gt),
+                (">=", BinaryOp::Ge),
+            ],
+            Self::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expr, ExprError> {
+        self.binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Self::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ExprError> {
+        self.binary(
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        self.descend()?;
+        let expr = self.unary_inner();
+        self.depth -= 1;
+        expr
+    }
+
+    fn unary_inner(&mut self) -> Result<Expr, ExprError> {
+        let op = match self.peek() {
+            Some(Token::Op("!")) => UnaryOp::Not,
+            Some(Token::Op("-")) => UnaryOp::Neg,
+            _ => return self.primary(),
+        };
+        self.idx += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let pos = self.position();
+        match self.next() {
+            Some(Token::Int(value)) => Ok(Expr::Literal(Scalar::Int(value))),
+            Some(Token::Float(value)) => Ok(Expr::Literal(Scalar::Float(value))),
+            Some(Token::Str(value)) => Ok(Expr::Literal(Scalar::Str(value))),
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(ExprError::parse(pos, "unclosed parenthesis")),
+                }
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Expr::Literal(Scalar::Bool(true))),
+                "false" => Ok(Expr::Literal(Scalar::Bool(false))),
+                "null" => Ok(Expr::Literal(Scalar::Null)),
+                _ if self.peek() == Some(&Token::LParen) => {
+                    self.idx += 1;
+                    let mut args = vec![];
+                    if self.peek() != Some(&Token::RParen) {
+                        loop {
+                            args.push(self.or()?);
+                            match self.peek() {
+                                Some(Token::Comma) => self.idx += 1,
+                                _ => break,
+                            }
+                        }
+                    }
+                    match self.next() {
+                        Some(Token::RParen) => {}
+                        _ => return Err(ExprError::parse(self.position(), "expected `)`")),
+                    }
+                    check_arity(pos, &ident, args.len())?;
+                    Ok(Expr::Call(ident, args))
+                }
+                _ => Ok(Expr::Property(ident)),
+            },
+            Some(token) => Err(ExprError::parse(pos, format!("unexpected {:?}", token))),
+            None => Err(ExprError::parse(pos, "unexpected end of expression")),
+        }
+    }
+}
+
+fn check_arity(pos: usize, function: &str, count: usize) -> Result<(), ExprError> {
+    let ok = match function {
+        "min" | "max" => count >= 1,
+        "abs" | "round" | "floor" | "ceil" => count == 1,
+        "if" => count == 3,
+        _ => {
+            return Err(ExprError::parse(
+                pos,
+                format!("unknown function `{}`", function),
+            ))
+        }
+    };
+    match ok {
+        true => Ok(()),
+        false => Err(ExprError::parse(
+            pos,
+            format!("wrong number of arguments for `{}`", function),
+        )),
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            idx: 0,
+            end: text.len(),
+            depth: 0,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(ExprError::parse(
+                parser.position(),
+                format!("unexpected {:?}", token),
+            )),
+        }
+    }
+
+    /// Names of the properties the expression reads
+    pub fn properties(&self) -> Vec<&str> {
+        match self {
+            Expr::Literal(_) => vec![],
+            Expr::Property(name) => vec![name.as_str()],
+            Expr::Unary(_, expr) => expr.properties(),
+            Expr::Binary(_, lhs, rhs) => {
+                let mut names = lhs.properties();
+                names.extend(rhs.properties());
+                names
+            }
+            Expr::Call(_, args) => args.iter().flat_map(Expr::properties).collect(),
+        }
+    }
+
+    /// Evaluates the expression, `lookup` resolving property names to their current value
+    pub fn eval(
+        &self,
+        lookup: &dyn Fn(&str) -> Result<Scalar, ExprError>,
+    ) -> Result<Scalar, ExprError> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Property(name) => lookup(name),
+            Expr::Unary(op, expr) => match (op, expr.eval(lookup)?) {
+                (UnaryOp::Not, Scalar::Bool(value)) => Ok(Scalar::Bool(!value)),
+                (UnaryOp::Neg, Scalar::Int(value)) => value
+                    .checked_neg()
+                    .map(Scalar::Int)
+                    .ok_or(ExprError::Overflow),
+                (UnaryOp::Neg, Scalar::Float(value)) => Ok(Scalar::Float(-value)),
+                (op, value) => Err(ExprError::Type(format!(
+                    "cannot apply {:?} to {}",
+                    op,
+                    value.type_name()
+                ))),
+            },
+            Expr::Binary(BinaryOp::And, lhs, rhs) => match lhs.eval(lookup)? {
+                Scalar::Bool(false) => Ok(Scalar::Bool(false)),
+                Scalar::Bool(true) => expect_bool(rhs.eval(lookup)?),
+                other => expect_bool(other),
+            },
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => match lhs.eval(lookup)? {
+                Scalar::Bool(true) => Ok(Scalar::Bool(true)),
+                Scalar::Bool(false) => expect_bool(rhs.eval(lookup)?),
+                other => expect_bool(other),
+            },
+            Expr::Binary(op, lhs, rhs) => binary(*op, lhs.eval(lookup)?, rhs.eval(lookup)?),
+            // Only the chosen branch is evaluated, so `if(x != 0, y / x, 0)` guards the division.
+            Expr::Call(function, args) if function == "if" => match args.as_slice() {
+                [condition, then, otherwise] => match expect_bool(condition.eval(lookup)?)? {
+                    Scalar::Bool(true) => then.eval(lookup),
+                    _ => otherwise.eval(lookup),
+                },
+                _ => Err(ExprError::Type(
+                    "wrong number of arguments for `if`".to_string(),
+                )),
+            },
+            Expr::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(lookup))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(function, args)
+            }
+        }
+    }
+}
+
+fn expect_bool(value: Scalar) -> Result<Scalar, ExprError> {
+    match value {
+        Scalar::Bool(_) => Ok(value),
+        other => Err(ExprError::Type(format!(
+            "expected bool, found {}",
+            other.type_name()
+        ))),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Scalar, rhs: Scalar) -> Result<Scalar, ExprError> {
+    use std::cmp::Ordering;
+
+    let ordering = |lhs: &Scalar, rhs: &Scalar| -> Option<Ordering> {
+        match (lhs, rhs) {
+            (Scalar::Int(a), Scalar::Int(b)) => Some(a.cmp(b)),
+            (Scalar::Str(a), Scalar::Str(b)) => Some(a.cmp(b)),
+            (Scalar::Bool(a), Scalar::Bool(b)) => Some(a.cmp(b)),
+            (Scalar::Null, Scalar::Null) => Some(Ordering::Equal),
+            _ => lhs.as_f64()?.partial_cmp(&rhs.as_f64()?),
+        }
+    };
+
+    match op {
+        BinaryOp::Eq => return Ok(Scalar::Bool(ordering(&lhs, &rhs) == Some(Ordering::Equal))),
+        BinaryOp::Ne => return Ok(Scalar::Bool(ordering(&lhs, &rhs) != Some(Ordering::Equal))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            // Comparisons with null are always false, so unset properties disable dependents.
+            let Some(ordering) = ordering(&lhs, &rhs).filter(|_| lhs != Scalar::Null) else {
+                return Ok(Scalar::Bool(false));
+            };
+            return Ok(Scalar::Bool(match op {
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::Le => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }));
+        }
+        _ => {}
+    }
+
+    match (op, &lhs, &rhs) {
+        (BinaryOp::Add, Scalar::Str(a), Scalar::Str(b)) => Ok(Scalar::Str(format!("{}{}", a, b))),
+        (_, Scalar::Int(a), Scalar::Int(b)) if op != BinaryOp::Div => match op {
+            BinaryOp::Add => a.checked_add(*b),
+            BinaryOp::Sub => a.checked_sub(*b),
+            BinaryOp::Mul => a.checked_mul(*b),
+            _ if *b == 0 => return Err(ExprError::DivisionByZero),
+            _ => a.checked_rem(*b),
+        }
+        .map(Scalar::Int)
+        .ok_or(ExprError::Overflow),
+        _ => {
+            let (Some(a), Some(b)) = (lhs.as_f64(), rhs.as_f64()) else {
+                return Err(ExprError::Type(format!(
+                    "cannot apply {:?} to {} and {}",
+                    op,
+                    lhs.type_name(),
+                    rhs.type_name()
+                )));
+            };
+            if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b == 0.0 {
+                return Err(ExprError::DivisionByZero);
+            }
+            Ok(Scalar::Float(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                _ => a % b,
+            }))
+        }
+    }
+}
+
+fn call(function: &str, mut args: Vec<Scalar>) -> Result<Scalar, ExprError> {
+    let number = |value: &Scalar| {
+        value.as_f64().ok_or(ExprError::Type(format!(
+            "`{}` expects numbers, found {}",
+            function,
+            value.type_name()
+        )))
+    };
+
+    match function {
+        "min" | "max" => {
+            let mut best = args.remove(0);
+            for arg in args {
+                let (a, b) = (number(&best)?, number(&arg)?);
+                if (function == "min" && b < a) || (function == "max" && b > a) {
+                    best = arg;
+                }
+            }
+            number(&best)?;
+            Ok(best)
+        }
+        "abs" => match &args[0] {
+            Scalar::Int(value) => value
+                .checked_abs()
+                .map(Scalar::Int)
+                .ok_or(ExprError::Overflow),
+            other => Ok(Scalar::Float(number(other)?.abs())),
+        },
+        _ => {
+            let value = number(&args[0])?;
+            let value = match function {
+                "round" => value.round(),
+                "floor" => value.floor(),
+                _ => value.ceil(),
+            };
+            match value.abs() < i64::MAX as f64 {
+                true => Ok(Scalar::Int(value as i64)),
+                false => Err(ExprError::Overflow),
+            }
+        }
+    }
+}
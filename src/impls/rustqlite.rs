@@ -23,10 +23,23 @@ impl DerefMut for RustQliteImpl {
 impl RustQliteImpl {
     pub async fn connect(path: &str) -> anyhow::Result<Self> {
         let conn = async_rusqlite::Connection::open(path).await?;
-        conn.call(|c| c.execute(include_str!("../../migrations/latest.sql"), ()))
+        conn.call(|c| c.execute_batch(include_str!("../../migrations/latest.sql")))
             .await?;
         return Ok(Self { connection: conn });
     }
+
+    /// Opens an existing database without ever writing to it, so no migrations are run either
+    pub async fn connect_read_only(path: &str) -> anyhow::Result<Self> {
+        use async_rusqlite::rusqlite::OpenFlags;
+
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = async_rusqlite::ConnectionBuilder::new()
+            .open_with_flags(path, flags)
+            .await?;
+        Ok(Self { connection: conn })
+    }
 }
 
 #[async_trait::async_trait]
@@ -35,9 +48,10 @@ impl StorageBackend for RustQliteImpl {
         Ok(self
             .connection
             .call(move |conn| {
-                let mut query = conn.prepare_cached("DELETE fROM schemas WHERE name = ?1")?;
-
-                query.execute([name])?;
+                let transaction = conn.transaction()?;
+                transaction.execute("DELETE FROM schemas WHERE name = ?1", [&name])?;
+                transaction.execute("DELETE FROM owners WHERE name = ?1", [&name])?;
+                transaction.commit()?;
                 Result::<_, async_rusqlite::Error>::Ok(())
             })
             .await?)
@@ -69,6 +83,31 @@ impl StorageBackend for RustQliteImpl {
         Ok(())
     }
 
+    async fn new_owned_schema(
+        &self,
+        name: String,
+        data: Vec<u8>,
+        app_id: Option<String>,
+        executable: Option<String>,
+    ) -> anyhow::Result<()> {
+        Ok(self
+            .connection
+            .call(move |conn| {
+                let transaction = conn.transaction()?;
+                transaction.execute(
+                    "INSERT INTO schemas (name, data) VALUES (?1, ?2)",
+                    (&name, data),
+                )?;
+                transaction.execute(
+                    "INSERT OR REPLACE INTO owners (name, app_id, executable) VALUES (?1, ?2, ?3)",
+                    (&name, app_id, executable),
+                )?;
+                transaction.commit()?;
+                Result::<_, async_rusqlite::Error>::Ok(())
+            })
+            .await?)
+    }
+
     async fn update_schema(&self, name: String, data: Vec<u8>) -> anyhow::Result<()> {
         self.connection
             .call(move |conn| {
@@ -80,7 +119,66 @@ impl StorageBackend for RustQliteImpl {
         Ok(())
     }
 
+    async fn set_owner(
+        &self,
+        name: String,
+        app_id: Option<String>,
+        executable: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.connection
+            .call(move |conn| {
+                let mut statement = conn.prepare_cached(
+                    "INSERT OR REPLACE INTO owners (name, app_id, executable) VALUES (?1, ?2, ?3)",
+                )?;
+                statement.execute((name, app_id, executable))
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn fetch_owner(
+        &self,
+        name: String,
+    ) -> anyhow::Result<Option<(Option<String>, Option<String>)>> {
+        Ok(self
+            .connection
+            .call(move |conn| {
+                let mut statement =
+                    conn.prepare_cached("SELECT app_id, executable FROM owners WHERE name = ?1")?;
+                let mut rows = statement.query_map([name], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                Result::<_, async_rusqlite::Error>::Ok(rows.next().transpose()?)
+            })
+            .await?)
+    }
+
+    async fn fetch_counter(&self, name: String) -> anyhow::Result<u64> {
+        let value = self
+            .connection
+            .call(move |conn| {
+                let mut statement =
+                    conn.prepare_cached("SELECT value FROM counters WHERE name = ?1")?;
+                let mut rows = statement.query_map([name], |row| row.get::<_, i64>(0))?;
+                Result::<_, async_rusqlite::Error>::Ok(rows.next().transpose()?)
+            })
+            .await?;
+        Ok(value.unwrap_or_default() as u64)
+    }
+
+    async fn raise_counter(&self, name: String, value: u64) -> anyhow::Result<()> {
+        self.connection
+            .call(move |conn| {
+                let mut statement = conn.prepare_cached(
+                    "INSERT INTO counters (name, value) VALUES (?1, ?2) \
+                     ON CONFLICT (name) DO UPDATE SET value = max(value, excluded.value)",
+                )?;
+                statement.execute((name, value as i64))
+            })
+            .await?;
+        Ok(())
+    }
+
     async fn fetch_schema(&self, name: String) -> anyhow::Result<Vec<u8>> {
+        let schema_name = name.clone();
         let s = self
             .connection
             .call(move |conn| {
@@ -98,7 +196,7 @@ impl StorageBackend for RustQliteImpl {
         if let Some(s) = s {
             Ok(s?)
         } else {
-            anyhow::bail!("Failed to fetch row")
+            Err(StorageError::SchemaNotFound(schema_name).into())
         }
     }
 }
@@ -0,0 +1,93 @@
+use crate::error::KeyFileError;
+
+/// A dconf style keyfile: one `[schema.name]` group per schema, followed by `key=value` lines
+/// with values in the GVariant text format. Lines starting with `#` or `;` are comments.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KeyFile {
+    pub groups: Vec<Group>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// 1-based line of the `[name]` header, 0 if the group was not parsed from text
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    /// 1-based line of the entry, 0 if the entry was not parsed from text
+    pub line: usize,
+}
+
+impl KeyFile {
+    pub fn parse(text: &str) -> Result<Self, KeyFileError> {
+        let mut keyfile = KeyFile::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .ok_or(KeyFileError::Syntax(
+                        line_number,
+                        "expected `[schema.name]`",
+                    ))?;
+                if keyfile.groups.iter().any(|group| group.name == name) {
+                    return Err(KeyFileError::DuplicateGroup(line_number, name.to_string()));
+                }
+                keyfile.groups.push(Group {
+                    name: name.to_string(),
+                    line: line_number,
+                    entries: vec![],
+                });
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(KeyFileError::Syntax(line_number, "expected `key=value`"))?;
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(KeyFileError::Syntax(line_number, "key is empty"));
+            }
+            let group = keyfile.groups.last_mut().ok_or(KeyFileError::Syntax(
+                line_number,
+                "key outside of a `[schema.name]` group",
+            ))?;
+            if group.entries.iter().any(|entry| entry.key == key) {
+                return Err(KeyFileError::DuplicateKey(line_number, key.to_string()));
+            }
+            group.entries.push(Entry {
+                key: key.to_string(),
+                value: value.trim().to_string(),
+                line: line_number,
+            });
+        }
+        Ok(keyfile)
+    }
+}
+
+impl std::fmt::Display for KeyFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, group) in self.groups.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", group.name)?;
+            for entry in &group.entries {
+                writeln!(f, "{}={}", entry.key, entry.value)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,18 @@
+#[cfg(feature = "definition")]
+pub mod definition;
 pub mod error;
+pub mod expr;
 #[cfg(any(feature = "dbus", feature = "tests"))]
 pub mod impls;
+pub mod keyfile;
+pub mod locale;
+#[cfg(feature = "dbus")]
+pub mod policy;
 pub mod property;
+#[cfg(feature = "dbus")]
+pub mod retry;
 pub mod schema;
+pub mod session;
 #[cfg(any(feature = "dbus", feature = "tests"))]
 pub mod storage_backend;
 pub mod value;
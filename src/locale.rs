@@ -0,0 +1,261 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::error::LocaleError;
+
+/// Translated `about` / `long_about` text of a property for a single locale.
+/// Empty strings fall back to the english text.
+#[derive(
+    Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize, zvariant::Type,
+)]
+pub struct Translation {
+    pub about: String,
+    pub long_about: String,
+}
+
+/// Returns the locale of the current process, following the usual `LC_ALL` > `LC_MESSAGES` > `LANG` order.
+/// Falls back to `C` if none of them are set.
+pub fn current_locale() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or("C".to_string())
+}
+
+/// Reduces a locale to the key translations are stored under, `pt-BR` and `pt_BR.UTF-8@euro` both
+/// becoming `pt_BR`.
+pub fn normalize(locale: &str) -> String {
+    locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('-', "_")
+}
+
+/// Expands a locale such as `de_DE.UTF-8@euro` into the keys to look up, most specific first: `["de_DE", "de"]`.
+pub fn fallback_chain(locale: &str) -> Vec<String> {
+    let locale = normalize(locale);
+
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return vec![];
+    }
+
+    let mut chain = vec![locale.clone()];
+    if let Some((language, _)) = locale.split_once('_') {
+        chain.push(language.to_string());
+    }
+    chain
+}
+
+/// A set of translated messages loaded from a gettext (`.po` / `.mo`) or Fluent (`.ftl`) file.
+///
+/// Gettext catalogs are keyed by the english text (the `msgid`), Fluent catalogs are keyed by
+/// `<property>-about` and `<property>-long-about` message ids.
+#[derive(Debug, Clone)]
+pub enum Catalog {
+    Gettext(HashMap<String, String>),
+    Fluent(HashMap<String, String>),
+}
+
+impl Catalog {
+    /// Loads a catalog, picking the format by file extension
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("po") => Self::from_po(&std::fs::read_to_string(path)?),
+            Some("mo") => Self::from_mo(&std::fs::read(path)?),
+            Some("ftl") => Self::from_ftl(&std::fs::read_to_string(path)?),
+            _ => Err(anyhow::Error::new(LocaleError::UnknownFormat)
+                .context(format!("While loading catalog {}", path.display()))),
+        }
+    }
+
+    pub fn from_po(text: &str) -> anyhow::Result<Self> {
+        #[derive(PartialEq)]
+        enum Field {
+            None,
+            Id,
+            Str,
+            Other,
+        }
+
+        let mut messages = HashMap::new();
+        let mut field = Field::None;
+        let (mut msgid, mut msgstr) = (String::new(), String::new());
+
+        let mut flush = |msgid: &mut String, msgstr: &mut String| {
+            if !msgid.is_empty() && !msgstr.is_empty() {
+                messages.insert(std::mem::take(msgid), std::mem::take(msgstr));
+            }
+            msgid.clear();
+            msgstr.clear();
+        };
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = match line.starts_with('"') {
+                true => ("", line),
+                false => line
+                    .split_once(char::is_whitespace)
+                    .ok_or(LocaleError::InvalidPo(
+                        idx + 1,
+                        "expected a keyword followed by a string",
+                    ))?,
+            };
+
+            match keyword {
+                "" => {}
+                "msgid" => {
+                    flush(&mut msgid, &mut msgstr);
+                    field = Field::Id;
+                }
+                "msgstr" | "msgstr[0]" => field = Field::Str,
+                "msgctxt" | "msgid_plural" => field = Field::Other,
+                _ if keyword.starts_with("msgstr[") => field = Field::Other,
+                _ => return Err(LocaleError::InvalidPo(idx + 1, "unknown keyword").into()),
+            }
+
+            let string = unquote_po(rest.trim())
+                .ok_or(LocaleError::InvalidPo(idx + 1, "expected a quoted string"))?;
+
+            match field {
+                Field::Id => msgid.push_str(&string),
+                Field::Str => msgstr.push_str(&string),
+                Field::Other => {}
+                Field::None => {
+                    return Err(
+                        LocaleError::InvalidPo(idx + 1, "string outside of an entry").into(),
+                    )
+                }
+            }
+        }
+        flush(&mut msgid, &mut msgstr);
+
+        Ok(Catalog::Gettext(messages))
+    }
+
+    pub fn from_mo(bytes: &[u8]) -> anyhow::Result<Self> {
+        let read_u32 = |offset: usize, le: bool| -> Result<u32, LocaleError> {
+            let slice: [u8; 4] = bytes
+                .get(offset..offset + 4)
+                .and_then(|s| s.try_into().ok())
+                .ok_or(LocaleError::InvalidMo)?;
+            Ok(match le {
+                true => u32::from_le_bytes(slice),
+                false => u32::from_be_bytes(slice),
+            })
+        };
+
+        let le = match read_u32(0, true)? {
+            0x950412de => true,
+            0xde120495 => false,
+            _ => return Err(LocaleError::InvalidMo.into()),
+        };
+
+        let read_str = |table: usize, idx: usize| -> Result<String, LocaleError> {
+            let len = read_u32(table + idx * 8, le)? as usize;
+            let offset = read_u32(table + idx * 8 + 4, le)? as usize;
+            let raw = bytes
+                .get(offset..offset + len)
+                .ok_or(LocaleError::InvalidMo)?;
+            // Plural forms are NUL separated, only the singular is used.
+            let raw = raw.split(|b| *b == 0).next().unwrap_or_default();
+            String::from_utf8(raw.to_vec()).map_err(|_| LocaleError::InvalidMo)
+        };
+
+        let count = read_u32(8, le)? as usize;
+        let originals = read_u32(12, le)? as usize;
+        let translations = read_u32(16, le)? as usize;
+
+        let mut messages = HashMap::new();
+        for idx in 0..count {
+            let msgid = read_str(originals, idx)?;
+            let msgstr = read_str(translations, idx)?;
+            // Skips the header entry and messages with a context (`ctx\x04msgid`).
+            if msgid.is_empty() || msgstr.is_empty() || msgid.contains('\u{4}') {
+                continue;
+            }
+            messages.insert(msgid, msgstr);
+        }
+
+        Ok(Catalog::Gettext(messages))
+    }
+
+    pub fn from_ftl(text: &str) -> anyhow::Result<Self> {
+        let mut messages: HashMap<String, String> = HashMap::new();
+        let mut current: Option<String> = None;
+
+        for (idx, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                let line = line.trim();
+                // Attributes are not used for properties.
+                if line.starts_with('.') {
+                    current = None;
+                    continue;
+                }
+                if let Some(value) = current.as_ref().and_then(|id| messages.get_mut(id)) {
+                    if !value.is_empty() {
+                        value.push('\n');
+                    }
+                    value.push_str(line);
+                }
+                continue;
+            }
+
+            let (id, value) = line
+                .split_once('=')
+                .ok_or(LocaleError::InvalidFtl(idx + 1, "expected `id = value`"))?;
+            let id = id.trim();
+            if id.is_empty() {
+                return Err(LocaleError::InvalidFtl(idx + 1, "message id is empty").into());
+            }
+            messages.insert(id.to_string(), value.trim().to_string());
+            current = Some(id.to_string());
+        }
+
+        Ok(Catalog::Fluent(messages))
+    }
+
+    pub(crate) fn about_for(&self, property: &str, english: &str) -> Option<&str> {
+        match self {
+            Catalog::Gettext(messages) => messages.get(english),
+            Catalog::Fluent(messages) => messages.get(&format!("{}-about", property)),
+        }
+        .map(String::as_str)
+    }
+
+    pub(crate) fn long_about_for(&self, property: &str, english: &str) -> Option<&str> {
+        match self {
+            Catalog::Gettext(messages) => messages.get(english),
+            Catalog::Fluent(messages) => messages.get(&format!("{}-long-about", property)),
+        }
+        .map(String::as_str)
+    }
+}
+
+fn unquote_po(string: &str) -> Option<String> {
+    let inner = string.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            other => out.push(other),
+        }
+    }
+    Some(out)
+}
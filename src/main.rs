@@ -25,6 +25,8 @@ struct WallpaperDaemon {
         writable = true,
         show_in_settings = true,
         default = wallpaper_default,
+        group = "Appearance",
+        widget = "file_chooser",
     )]
     path: String,
     #[field(
@@ -73,6 +75,38 @@ async fn test_fetch_all() {
     println!("{:#?}", schemas.await.unwrap());
 }
 
+#[cfg(feature = "tests")]
+#[tokio::test]
+async fn test_schema_locks() {
+    use gludconfig::storage::Storage;
+
+    const NAME: &str = "org.glud.test.locks";
+    let db = Storage::new().await.unwrap();
+    let first = db.lock_schema(NAME).await;
+    assert_eq!(db.schema_lock_count(), 1);
+
+    // The lock stays while someone waits for it, and goes once the last guard is dropped
+    let second = db.lock_schema(NAME);
+    tokio::pin!(second);
+    let waiting = tokio::time::timeout(std::time::Duration::from_millis(10), &mut second).await;
+    assert_eq!(waiting.is_err(), true);
+    drop(first);
+    assert_eq!(db.schema_lock_count(), 1);
+    drop(second.await);
+    assert_eq!(db.schema_lock_count(), 0);
+}
+
+#[cfg(feature = "tests")]
+#[tokio::test]
+async fn test_serials() {
+    use gludconfig::storage::Storage;
+
+    let first = Storage::new().await.unwrap().next_serials(2).await.unwrap();
+    // A new daemon continues after the serials handed out by the previous one
+    let db = Storage::new().await.unwrap();
+    assert_eq!(db.next_serials(1).await.unwrap() >= first + 2, true);
+}
+
 #[cfg(feature = "tests")]
 #[tokio::test]
 async fn test_crud_speed() {
@@ -149,6 +183,35 @@ async fn test_fetch_schema() {
     assert_eq!(schema.is_ok(), true);
 }
 
+#[cfg(feature = "tests")]
+#[test]
+fn test_decode_v0_schema() {
+    use gludconfig::storage::Storage;
+
+    // Written by `Storage::new_schema` before the stored format was versioned
+    let bytes = include_bytes!("../tests/fixtures/schema_v0.bin");
+    let schema = Storage::decode_schema(bytes).unwrap();
+    assert_eq!(schema.name(), "org.glud.legacy");
+    assert_eq!(schema.version(), 3);
+    let scale = schema.properties().find(|p| p.name() == "scale").unwrap();
+    assert_eq!(scale.get_value(), Some(&OwnedValue::from(1.5f64)));
+    assert_eq!(scale.group(), "");
+    assert_eq!(scale.computed(), None);
+    let theme = schema.properties().find(|p| p.name() == "theme").unwrap();
+    assert_eq!(theme.is_writable(), false);
+    assert_eq!(theme.get_value(), None);
+    assert_eq!(schema.triggers().next().unwrap().name(), "reload");
+
+    // Rewritten blobs carry the current format version and read back the same
+    let bytes = Storage::encode_schema(&schema).unwrap();
+    assert_eq!(&bytes[..4], b"GLUD");
+    let reread = Storage::decode_schema(&bytes).unwrap();
+    assert_eq!(reread.properties().count(), 2);
+    let mut future = bytes.clone();
+    future[4] = 99;
+    assert_eq!(Storage::decode_schema(&future).is_err(), true);
+}
+
 #[cfg(feature = "tests")]
 #[tokio::test]
 async fn test_update_writable_property() {
@@ -295,6 +358,29 @@ async fn test_dbus_read() {
     }
 }
 
+#[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
+#[tokio::test]
+async fn test_dbus_load_all() {
+    let conn = zbus::Connection::session().await.unwrap();
+
+    #[glud_macros::glud_interface(name = "org.desktop.ui.wallpaper", blocking = false)]
+    trait WallpaperDaemon {
+        #[property(name = "wallpaper_path")]
+        async fn wallpaper_path() -> String;
+        #[property(name = "some_property")]
+        async fn some_property() -> Vec<String>;
+    }
+
+    let daemon = WallpaperDaemon::new(&conn).await.unwrap();
+    let values = daemon.load_all().await.unwrap();
+    let info = daemon.info_wallpaper_path().await.unwrap();
+    let current: Option<OwnedValue> = info.current.into();
+    assert_eq!(
+        values.wallpaper_path,
+        current.map(|value| String::try_from(value).unwrap())
+    );
+}
+
 #[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
 #[tokio::test]
 async fn test_dbus_write() {
@@ -348,14 +434,195 @@ async fn test_dbus_reset() {
     let was_reset = daemon.reset_wallpaper_path().await.unwrap();
     assert_eq!(was_reset, true);
     assert_eq!(
-        daemon.info_wallpaper_path().await.unwrap().6,
-        (
-            false,
+        daemon.info_wallpaper_path().await.unwrap().current,
+        gludconfig::value::Nullable::from(Some(
             zvariant::Value::from(wallpaper_default().unwrap()).into()
-        )
+        ))
     )
 }
 
+#[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
+#[tokio::test]
+async fn test_dbus_delay() {
+    let conn = zbus::Connection::session().await.unwrap();
+    let other = zbus::Connection::session().await.unwrap();
+
+    #[glud_macros::glud_interface(name = "org.desktop.ui.wallpaper", blocking = false)]
+    trait WallpaperDaemon {
+        #[property(name = "wallpaper_path")]
+        async fn wallpaper_path() -> String;
+    }
+
+    let daemon = WallpaperDaemon::new(&conn).await.unwrap();
+    let observer = WallpaperDaemon::new(&other).await.unwrap();
+    let before = observer.load_all().await.unwrap().wallpaper_path;
+
+    daemon.delay().await.unwrap();
+    daemon
+        .set_wallpaper_path(Some("/tmp/staged.png".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(daemon.has_unapplied().await.unwrap(), true);
+    assert_eq!(
+        daemon.load_all().await.unwrap().wallpaper_path.as_deref(),
+        Some("/tmp/staged.png")
+    );
+    assert_eq!(observer.load_all().await.unwrap().wallpaper_path, before);
+
+    assert_eq!(daemon.revert().await.unwrap(), true);
+    assert_eq!(daemon.load_all().await.unwrap().wallpaper_path, before);
+
+    daemon.delay().await.unwrap();
+    daemon
+        .set_wallpaper_path(Some("/tmp/applied.png".to_string()))
+        .await
+        .unwrap();
+    daemon.apply().await.unwrap();
+    assert_eq!(daemon.has_unapplied().await.unwrap(), false);
+    assert_eq!(
+        observer.load_all().await.unwrap().wallpaper_path.as_deref(),
+        Some("/tmp/applied.png")
+    );
+}
+
+#[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
+#[tokio::test]
+async fn test_dbus_set_if() {
+    let conn = zbus::Connection::session().await.unwrap();
+
+    #[glud_macros::glud_interface(name = "org.desktop.ui.wallpaper", blocking = false)]
+    trait WallpaperDaemon {
+        #[property(name = "wallpaper_path")]
+        async fn wallpaper_path() -> String;
+    }
+
+    let daemon = WallpaperDaemon::new(&conn).await.unwrap();
+    // An expected null matches the null the daemon stores
+    daemon.set_wallpaper_path(None).await.unwrap();
+    daemon
+        .set_wallpaper_path_if(None, Some("/tmp/a.png".to_string()))
+        .await
+        .unwrap();
+    let err = daemon
+        .set_wallpaper_path_if(None, Some("/tmp/b.png".to_string()))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        matches!(err, gludconfig::error::DaemonError::ValueChanged(_)),
+        true
+    );
+
+    let err = daemon
+        .set_wallpaper_path_if(
+            Some("/tmp/b.png".to_string()),
+            Some("/tmp/c.png".to_string()),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        matches!(err, gludconfig::error::DaemonError::ValueChanged(_)),
+        true
+    );
+    daemon
+        .set_wallpaper_path_if(
+            Some("/tmp/a.png".to_string()),
+            Some("/tmp/c.png".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let updated = daemon
+        .update_wallpaper_path(|path| path.map(|path| path.replace(".png", ".svg")))
+        .await
+        .unwrap();
+    assert_eq!(updated.as_deref(), Some("/tmp/c.svg"));
+    assert_eq!(
+        daemon.load_all().await.unwrap().wallpaper_path.as_deref(),
+        Some("/tmp/c.svg")
+    );
+}
+
+#[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
+#[test]
+fn test_dbus_update_gives_up() {
+    #[glud_macros::glud_interface(name = "org.desktop.ui.wallpaper", blocking = true)]
+    trait WallpaperDaemon {
+        #[property(name = "wallpaper_path")]
+        fn wallpaper_path() -> String;
+    }
+
+    let conn = zbus::blocking::Connection::session().unwrap();
+    let daemon = WallpaperDaemon::new(&conn).unwrap();
+    let other = WallpaperDaemon::new(&zbus::blocking::Connection::session().unwrap()).unwrap();
+
+    // Another client changes the value every time before `update` gets to write it
+    let mut attempts = 0;
+    let err = daemon
+        .update_wallpaper_path(|path| {
+            attempts += 1;
+            other
+                .set_wallpaper_path(Some(format!("/tmp/other-{}.png", attempts)))
+                .unwrap();
+            path
+        })
+        .unwrap_err();
+    assert_eq!(
+        matches!(err, gludconfig::error::DaemonError::ValueChanged(_)),
+        true
+    );
+    assert_eq!(attempts > 1, true);
+}
+
+#[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_dbus_concurrent_writes() {
+    #[glud_macros::glud_interface(name = "org.desktop.ui.wallpaper", blocking = false)]
+    trait WallpaperDaemon {
+        #[property(name = "wallpaper_path")]
+        async fn wallpaper_path() -> String;
+        #[property(name = "some_property")]
+        async fn some_property() -> Vec<String>;
+    }
+
+    const CLIENTS: usize = 8;
+    const UPDATES: usize = 25;
+
+    let conn = zbus::Connection::session().await.unwrap();
+    let daemon = WallpaperDaemon::new(&conn).await.unwrap();
+    daemon
+        .set_wallpaper_path(Some("/tmp/stress-".to_string()))
+        .await
+        .unwrap();
+
+    // Every client appends to the path while also writing another key of the same schema, which
+    // would write back a stale path if the daemon did not serialize the writes.
+    let clients = (0..CLIENTS).map(|_| {
+        tokio::spawn(async {
+            let conn = zbus::Connection::session().await.unwrap();
+            let daemon = WallpaperDaemon::new(&conn).await.unwrap();
+            for _ in 0..UPDATES {
+                daemon
+                    .update_wallpaper_path(|path| path.map(|path| path + "x"))
+                    .await
+                    .unwrap();
+                daemon
+                    .set_some_property(Some(vec!["foo".to_string()]))
+                    .await
+                    .unwrap();
+            }
+        })
+    });
+    for client in clients.collect::<Vec<_>>() {
+        client.await.unwrap();
+    }
+
+    let path = daemon.load_all().await.unwrap().wallpaper_path.unwrap();
+    assert_eq!(
+        path,
+        format!("/tmp/stress-{}", "x".repeat(CLIENTS * UPDATES))
+    );
+}
+
 #[cfg(all(feature = "dbus", feature = "tests", feature = "macros"))]
 #[tokio::test]
 async fn test_dbus_register() {
@@ -389,3 +656,964 @@ async fn test_dbus_register() {
         .await
         .unwrap();
 }
+
+#[cfg(all(feature = "dbus", feature = "tests"))]
+#[tokio::test]
+async fn test_dbus_register_owner() {
+    use gludconfig::{error::DaemonError, policy::Owner, storage::Storage, value::Nullable};
+
+    const NAME: &str = "org.glud.test.owned";
+    let definition = |extra: &str| {
+        format!(
+            "name = \"{}\"\nversion = 1\n\n[[properties]]\nname = \"flag\"\nsignature = \"b\"\ndefault = false\n{}",
+            NAME, extra
+        )
+    };
+    let conn = zbus::Connection::session().await.unwrap();
+    let proxy = |path: &'static str, interface: &'static str| {
+        zbus::Proxy::new(&conn, "org.glud.GludConfig", path, interface)
+    };
+    let schemas = proxy("/org/glud/gludconfig/schema", "org.glud.GludConfig.Schema")
+        .await
+        .unwrap();
+    let properties = proxy(
+        "/org/glud/gludconfig/property",
+        "org.glud.GludConfig.Property",
+    )
+    .await
+    .unwrap();
+    let register = |method: &'static str, definition: String| {
+        let schemas = &schemas;
+        async move {
+            schemas
+                .call::<_, _, ()>(method, &(definition, "toml"))
+                .await
+                .map_err(DaemonError::from)
+        }
+    };
+    let db = Storage::new().await.unwrap();
+    let _ = db.delete_schema(NAME.to_string()).await;
+
+    register("register_definition", definition(""))
+        .await
+        .unwrap();
+    properties
+        .call::<_, _, ()>("set", &(NAME, "flag", Nullable::wrap(Some(true))))
+        .await
+        .unwrap();
+
+    // The owner may update the definition, values of unchanged properties are kept
+    let level = "\n[[properties]]\nname = \"level\"\nsignature = \"u\"\ndefault = 3\n";
+    register("register_definition", definition(level))
+        .await
+        .unwrap();
+    let values = db.get_schema(NAME.to_string()).await.unwrap().values();
+    assert_eq!(values["flag"], Nullable::wrap(Some(true)));
+    assert_eq!(values["level"], Nullable::wrap(Some(3u32)));
+
+    // Anyone else is refused
+    let foreign = Owner {
+        app_id: Some("org.glud.Other".to_string()),
+        executable: None,
+    };
+    db.set_owner(NAME.to_string(), &foreign).await.unwrap();
+    let err = register("register_definition", definition(""))
+        .await
+        .unwrap_err();
+    assert_eq!(matches!(err, DaemonError::PermissionDenied(_)), true);
+    let err = schemas
+        .call::<_, _, ()>("delete", &(NAME,))
+        .await
+        .map_err(DaemonError::from)
+        .unwrap_err();
+    assert_eq!(matches!(err, DaemonError::PermissionDenied(_)), true);
+
+    // Overriding the owner takes admin access, which only a policy rule grants, or being root
+    let root = std::os::unix::fs::MetadataExt::uid(&std::fs::metadata("/proc/self").unwrap()) == 0;
+    let overridden = register("register_definition_override", definition("")).await;
+    let deleted = schemas
+        .call::<_, _, ()>("delete_override", &(NAME,))
+        .await
+        .map_err(DaemonError::from);
+    if root {
+        overridden.unwrap();
+        deleted.unwrap();
+        return;
+    }
+    let err = overridden.unwrap_err();
+    assert_eq!(matches!(err, DaemonError::PermissionDenied(_)), true);
+    let err = deleted.unwrap_err();
+    assert_eq!(matches!(err, DaemonError::PermissionDenied(_)), true);
+    assert_eq!(db.owner(NAME.to_string()).await.unwrap(), Some(foreign));
+
+    db.delete_schema(NAME.to_string()).await.unwrap();
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_property_info_v0() {
+    use gludconfig::{property::PropertyInfoV0, value::Nullable};
+    use zvariant::{OwnedSignature, Type};
+
+    // `metadata` must keep replying with the layout older clients decode
+    assert_eq!(
+        PropertyInfoV0::signature(),
+        <(bool, String, String, String, bool, OwnedSignature, Nullable)>::signature()
+    );
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_localized_about() {
+    use gludconfig::{locale::Catalog, property::Property};
+
+    let mut property = Property::builder()
+        .name("wallpaper_path".to_string())
+        .about("The path of the wallpaper".to_string())
+        .about_translation("fr".to_string(), "Le chemin du fond d'écran".to_string())
+        .signature(<String as zvariant::Type>::signature())
+        .build()
+        .unwrap();
+
+    let catalog = Catalog::from_po(
+        "msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=UTF-8\\n\"\n\nmsgid \"The path of the wallpaper\"\nmsgstr \"Der Pfad des \"\n\"Hintergrundbildes\"\n",
+    )
+    .unwrap();
+    property.translate("de", &catalog);
+
+    assert_eq!(
+        property.about_localized("de_DE.UTF-8"),
+        "Der Pfad des Hintergrundbildes"
+    );
+    assert_eq!(
+        property.about_localized("fr_FR"),
+        "Le chemin du fond d'écran"
+    );
+    assert_eq!(property.about_localized("C"), "The path of the wallpaper");
+    assert_eq!(
+        property.long_about_localized("de"),
+        "No description provided"
+    );
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_load_translations() {
+    use gludconfig::{property::Property, schema::Schema};
+
+    let dir = std::env::temp_dir().join(format!("glud-translations-{}", std::process::id()));
+    let po = |text: &str| format!("msgid \"The path of the wallpaper\"\nmsgstr \"{}\"\n", text);
+    let messages = dir.join("de").join("LC_MESSAGES");
+    std::fs::create_dir_all(&messages).unwrap();
+    std::fs::write(messages.join("org.foo.wallpaper.po"), po("Der Pfad")).unwrap();
+    std::fs::write(messages.join("other.po"), po("Ein anderer Pfad")).unwrap();
+    let messages = dir.join("pt-BR").join("LC_MESSAGES");
+    std::fs::create_dir_all(&messages).unwrap();
+    std::fs::write(messages.join("org.foo.wallpaper.po"), po("O caminho")).unwrap();
+
+    let mut schema = Schema::builder()
+        .name("org.foo.wallpaper".to_string())
+        .version(1)
+        .property(
+            Property::builder()
+                .name("wallpaper_path".to_string())
+                .about("The path of the wallpaper".to_string())
+                .signature(<String as zvariant::Type>::signature())
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let loaded = schema.load_translations(&dir, "org.foo.wallpaper");
+    std::fs::remove_dir_all(&dir).unwrap();
+    loaded.unwrap();
+
+    // Catalogs of other gettext domains in the same directory are left alone
+    let property = schema.resolve("wallpaper_path").unwrap();
+    assert_eq!(property.about_localized("de_DE"), "Der Pfad");
+    // Directory names are stored the way locales are looked up
+    assert_eq!(property.about_localized("pt_BR.UTF-8"), "O caminho");
+    assert_eq!(property.about_localized("pt-BR"), "O caminho");
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_catalog_formats() {
+    use gludconfig::locale::Catalog;
+
+    // A header entry, a message with a context and a message with a plural form
+    let entries: [(&str, &str); 3] = [
+        ("", "Content-Type: text/plain; charset=UTF-8\n"),
+        ("menu\u{4}Open", "Öffnen"),
+        ("The path\0The paths", "Der Pfad\0Die Pfade"),
+    ];
+    let mo = |le: bool| {
+        let u32_bytes = |value: usize| match le {
+            true => (value as u32).to_le_bytes(),
+            false => (value as u32).to_be_bytes(),
+        };
+        // Header, then the msgid table, the msgstr table and the NUL terminated strings
+        let (originals, translations) = (28, 28 + 8 * entries.len());
+        let mut offset = translations + 8 * entries.len();
+        let (mut tables, mut strings) = (vec![], vec![]);
+        for texts in [entries.map(|(id, _)| id), entries.map(|(_, text)| text)] {
+            for text in texts {
+                tables.extend(u32_bytes(text.len()));
+                tables.extend(u32_bytes(offset));
+                strings.extend(text.as_bytes());
+                strings.push(0);
+                offset += text.len() + 1;
+            }
+        }
+        let mut bytes = vec![];
+        for value in [0x950412de, 0, entries.len(), originals, translations, 0, 0] {
+            bytes.extend(u32_bytes(value));
+        }
+        bytes.extend(tables);
+        bytes.extend(strings);
+        bytes
+    };
+
+    for le in [true, false] {
+        let catalog = Catalog::from_mo(&mo(le)).unwrap();
+        let Catalog::Gettext(messages) = catalog else {
+            panic!("expected a gettext catalog");
+        };
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages.get("The path").map(String::as_str),
+            Some("Der Pfad")
+        );
+    }
+    assert_eq!(Catalog::from_mo(b"not a catalog").is_err(), true);
+
+    let catalog = Catalog::from_ftl(
+        "# Comment\nwallpaper_path-about = Der Pfad\nwallpaper_path-long-about =\n    Der Pfad\n    des Bildes\n    .title = ignored\n",
+    )
+    .unwrap();
+    let Catalog::Fluent(messages) = catalog else {
+        panic!("expected a fluent catalog");
+    };
+    assert_eq!(
+        messages.get("wallpaper_path-about").map(String::as_str),
+        Some("Der Pfad")
+    );
+    assert_eq!(
+        messages
+            .get("wallpaper_path-long-about")
+            .map(String::as_str),
+        Some("Der Pfad\ndes Bildes")
+    );
+    assert_eq!(Catalog::from_ftl("no equals sign").is_err(), true);
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_deprecated_alias() {
+    use gludconfig::{property::Property, schema::Schema};
+
+    let property = |name: &str| {
+        Property::builder()
+            .name(name.to_string())
+            .signature(<u32 as zvariant::Type>::signature())
+    };
+
+    let schema = Schema::builder()
+        .name("org.foo.deprecated".to_string())
+        .version(1)
+        .property(property("scale").build().unwrap())
+        .property(
+            property("zoom")
+                .deprecated("Use `scale` instead".to_string())
+                .replaced_by("scale".to_string())
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+
+    assert_eq!(schema.resolve("zoom").unwrap().name(), "scale");
+    assert_eq!(schema.resolve("scale").unwrap().name(), "scale");
+    assert_eq!(schema.keys_of("scale"), vec!["scale", "zoom"]);
+
+    let missing = Schema::builder()
+        .name("org.foo.deprecated".to_string())
+        .version(1)
+        .property(
+            property("zoom")
+                .replaced_by("scale".to_string())
+                .build()
+                .unwrap(),
+        )
+        .build();
+    assert_eq!(missing.is_err(), true);
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_enabled_when() {
+    use gludconfig::{property::Property, schema::Schema, value::Value};
+
+    let mut schema = Schema::builder()
+        .name("org.foo.power".to_string())
+        .version(1)
+        .property(
+            Property::builder()
+                .name("auto_suspend".to_string())
+                .signature(<bool as zvariant::Type>::signature())
+                .default(Value::wrap(Some(false)))
+                .build()
+                .unwrap(),
+        )
+        .property(
+            Property::builder()
+                .name("suspend_delay".to_string())
+                .signature(<u32 as zvariant::Type>::signature())
+                .enabled_when("auto_suspend == true && 1 + 2 * 3 >= 7".to_string())
+                .reject_when_disabled(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+
+    assert_eq!(schema.is_enabled("suspend_delay").unwrap(), false);
+    assert_eq!(schema.check_enabled("suspend_delay").is_err(), true);
+
+    schema
+        .resolve_mut("auto_suspend")
+        .unwrap()
+        .set_value(Value::wrap(Some(true)))
+        .unwrap();
+    assert_eq!(schema.is_enabled("suspend_delay").unwrap(), true);
+    assert_eq!(schema.check_enabled("suspend_delay").is_ok(), true);
+
+    assert_eq!(
+        Property::builder()
+            .name("broken".to_string())
+            .signature(<u32 as zvariant::Type>::signature())
+            .enabled_when("auto_suspend ==".to_string())
+            .build()
+            .is_err(),
+        true
+    );
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_computed_property() {
+    use gludconfig::{property::Property, schema::Schema, value::Value};
+
+    let property = |name: &str, value: f64| {
+        Property::builder()
+            .name(name.to_string())
+            .signature(<f64 as zvariant::Type>::signature())
+            .default(Value::wrap(Some(value)))
+            .build()
+            .unwrap()
+    };
+
+    let mut schema = Schema::builder()
+        .name("org.foo.display".to_string())
+        .version(1)
+        .property(property("scale", 2.0))
+        .property(property("fractional_factor", 1.25))
+        .property(
+            Property::builder()
+                .name("effective_scale".to_string())
+                .signature(<f64 as zvariant::Type>::signature())
+                .computed("scale * fractional_factor".to_string())
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+
+    let effective = |schema: &Schema| {
+        schema
+            .resolve("effective_scale")
+            .unwrap()
+            .get_value()
+            .cloned()
+    };
+    assert_eq!(effective(&schema), Some(zvariant::Value::from(2.5).into()));
+    assert_eq!(
+        schema.resolve("effective_scale").unwrap().is_writable(),
+        false
+    );
+
+    schema
+        .resolve_mut("scale")
+        .unwrap()
+        .set_value(Value::wrap(Some(4.0)))
+        .unwrap();
+    assert_eq!(schema.recompute(), vec!["effective_scale"]);
+    assert_eq!(effective(&schema), Some(zvariant::Value::from(5.0).into()));
+    assert_eq!(schema.recompute().is_empty(), true);
+
+    let cyclic = Schema::builder()
+        .name("org.foo.cyclic".to_string())
+        .version(1)
+        .property(
+            Property::builder()
+                .name("a".to_string())
+                .signature(<f64 as zvariant::Type>::signature())
+                .computed("a + 1".to_string())
+                .build()
+                .unwrap(),
+        )
+        .build();
+    assert_eq!(cyclic.is_err(), true);
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_expr() {
+    use gludconfig::expr::{Expr, Scalar};
+
+    let guarded = Expr::parse("if(x != 0, y / x, 0)").unwrap();
+    let eval = |x: i64| {
+        guarded.eval(&|name: &str| match name {
+            "x" => Ok(Scalar::Int(x)),
+            _ => Ok(Scalar::Int(10)),
+        })
+    };
+    assert_eq!(eval(0).ok(), Some(Scalar::Int(0)));
+    assert_eq!(eval(5).ok(), Some(Scalar::Float(2.0)));
+    assert_eq!(
+        Expr::parse("if(1, 2, 3)")
+            .unwrap()
+            .eval(&|_| Ok(Scalar::Null))
+            .is_err(),
+        true
+    );
+
+    // Nesting is bounded so neither parsing nor evaluation can overflow the stack
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(Expr::parse(&nested(10)).is_ok(), true);
+    assert_eq!(Expr::parse(&nested(100_000)).is_err(), true);
+    assert_eq!(Expr::parse(&"-".repeat(100_000)).is_err(), true);
+    assert_eq!(
+        Expr::parse(&format!("{}1", "1 + ".repeat(100_000))).is_err(),
+        true
+    );
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_session() {
+    use gludconfig::{
+        property::Property, schema::Schema, session::Session, value::Nullable, value::Value,
+    };
+
+    let property = |name: &str| {
+        Property::builder()
+            .name(name.to_string())
+            .signature(<f64 as zvariant::Type>::signature())
+            .default(Value::wrap(Some(1.0)))
+            .choices(vec![Value::wrap(Some(1.0)), Value::wrap(Some(2.0))])
+            .build()
+            .unwrap()
+    };
+    let schema = || {
+        Schema::builder()
+            .name("org.foo.display".to_string())
+            .version(1)
+            .property(property("scale"))
+            .property(
+                Property::builder()
+                    .name("double_scale".to_string())
+                    .signature(<f64 as zvariant::Type>::signature())
+                    .computed("scale * 2".to_string())
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    };
+    let value = |value: f64| Nullable::from(Some(zvariant::Value::from(value).into()));
+
+    let mut session = Session::default();
+    assert_eq!(session.is_empty(), true);
+    session.set(schema(), "scale", value(2.0)).unwrap();
+    assert_eq!(session.set(schema(), "scale", value(3.0)).is_err(), true);
+    assert_eq!(session.set(schema(), "missing", value(2.0)).is_err(), true);
+
+    let mut staged = schema();
+    assert_eq!(
+        session.apply(&mut staged).unwrap(),
+        vec!["scale", "double_scale"]
+    );
+    assert_eq!(staged.values()["scale"], value(2.0));
+    assert_eq!(staged.values()["double_scale"], value(4.0));
+    assert_eq!(schema().values()["scale"], value(1.0));
+
+    assert_eq!(session.reset(schema(), "scale").unwrap(), true);
+    let mut staged = schema();
+    session.apply(&mut staged).unwrap();
+    assert_eq!(staged.values()["scale"], value(1.0));
+
+    // Writes that stopped applying are skipped by reads, only `apply` reports them
+    let mut session = Session::default();
+    session
+        .set_many(schema(), vec![("scale".to_string(), value(2.0))])
+        .unwrap();
+    let read_only = || {
+        Schema::builder()
+            .name("org.foo.display".to_string())
+            .version(1)
+            .property(
+                Property::builder()
+                    .name("scale".to_string())
+                    .signature(<f64 as zvariant::Type>::signature())
+                    .default(Value::wrap(Some(1.0)))
+                    .writable(false)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    };
+    let mut staged = read_only();
+    session.preview(&mut staged);
+    assert_eq!(staged.values()["scale"], value(1.0));
+    assert_eq!(session.apply(&mut read_only()).is_err(), true);
+    // Resetting a read-only property reports false, as it does outside of a session
+    let mut resets = Session::default();
+    assert_eq!(resets.reset(read_only(), "scale").unwrap(), false);
+    assert_eq!(resets.is_empty(), true);
+    assert_eq!(resets.reset(read_only(), "missing").is_err(), true);
+    let mut staged = schema();
+    session.preview(&mut staged);
+    assert_eq!(staged.values()["scale"], value(2.0));
+    assert_eq!(
+        session
+            .set_many(
+                schema(),
+                vec![
+                    ("scale".to_string(), value(1.0)),
+                    ("scale".to_string(), value(3.0))
+                ]
+            )
+            .is_err(),
+        true
+    );
+    let mut staged = schema();
+    session.apply(&mut staged).unwrap();
+    assert_eq!(staged.values()["scale"], value(2.0));
+}
+
+#[cfg(all(feature = "dbus", feature = "tests"))]
+#[test]
+fn test_policy() {
+    use gludconfig::policy::{Access, Caller, Owner, Policy};
+
+    let policy = Policy::parse(
+        r#"
+default = "read"
+
+[[rules]]
+schema = "org.foo"
+access = "none"
+
+[[rules]]
+schema = "org.foo.security"
+executable = "/usr/bin/foo-settings"
+access = "write"
+
+[[rules]]
+schema = "org.foo.security"
+uid = 1000
+access = "read"
+"#,
+    )
+    .unwrap();
+    let caller = |executable: &str, uid: u32| Caller {
+        uid: Some(uid),
+        pid: Some(1),
+        executable: Some(executable.to_string()),
+        app_id: None,
+    };
+
+    let settings = caller("/usr/bin/foo-settings", 1001);
+    let browser = caller("/usr/bin/browser", 1000);
+    assert_eq!(policy.access(&settings, "org.foo.security"), Access::Write);
+    assert_eq!(policy.access(&browser, "org.foo.security"), Access::Read);
+    assert_eq!(
+        policy.access(&caller("/usr/bin/browser", 0), "org.foo.security"),
+        Access::None
+    );
+    assert_eq!(policy.access(&browser, "org.foo.display"), Access::None);
+    assert_eq!(policy.access(&browser, "org.foobar"), Access::Read);
+    assert_eq!(policy.allows_everyone(Access::Read), false);
+    assert_eq!(policy.allows_everyone(Access::None), true);
+    // Without a policy file everyone may write, only root is an admin
+    let default = Policy::default();
+    assert_eq!(default.allows_everyone(Access::Write), true);
+    assert_eq!(default.allows_everyone(Access::Admin), false);
+    assert_eq!(default.access(&browser, "org.foo.security"), Access::Write);
+    assert_eq!(
+        default.access(&caller("/usr/bin/browser", 0), "org.foo.security"),
+        Access::Admin
+    );
+    assert_eq!(Policy::parse("default = \"all\"").is_err(), true);
+
+    let owner = Owner::of(&settings);
+    assert_eq!(owner.is(&settings), true);
+    assert_eq!(owner.is(&browser), false);
+    let sandboxed = Caller {
+        app_id: Some("org.foo.Settings".to_string()),
+        ..browser.clone()
+    };
+    let owner = Owner::of(&sandboxed);
+    assert_eq!(owner.is(&sandboxed), true);
+    // The app id decides, whatever runtime binary the sandbox runs
+    assert_eq!(owner.is(&settings), false);
+    // Callers that could not be identified own nothing, not even what they registered
+    let unknown = Owner::of(&Caller::default());
+    assert_eq!(unknown.to_string(), "an unknown application");
+    assert_eq!(unknown.is(&Caller::default()), false);
+    assert_eq!(unknown.is(&settings), false);
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_value_text_format() {
+    use gludconfig::value::Value;
+    use zvariant::Signature;
+
+    let round_trip = |sig: &str, text: &str| {
+        let sig = Signature::try_from(sig).unwrap();
+        let value = Value::parse(text, &sig).unwrap();
+        assert_eq!(value.signature(), sig);
+        assert_eq!(value.to_string(), text);
+        assert_eq!(Value::parse(&value.to_string(), &sig).unwrap(), value);
+    };
+
+    round_trip("u", "42");
+    round_trip("x", "-9223372036854775808");
+    round_trip("d", "1.5");
+    round_trip("b", "true");
+    round_trip("s", "'it\\'s\\n'");
+    round_trip("o", "'/org/foo'");
+    round_trip("(ii)", "(1, 2)");
+    round_trip("(s)", "('a',)");
+    round_trip("as", "['a', 'b']");
+    round_trip("as", "[]");
+    round_trip("aai", "[[1, 2], [], [3]]");
+    round_trip("a{sv}", "{'k': <1>, 'l': <uint32 2>, 'm': <@as []>}");
+    round_trip("a{s(ib)}", "{'k': (1, true)}");
+    round_trip("v", "<('a', [1.0, 2.5])>");
+    round_trip("v", "<(1,)>");
+    round_trip("ms", "just 'a'");
+    round_trip("ms", "nothing");
+    round_trip("u", "nothing");
+
+    let sig = |sig: &'static str| Signature::from_static_str(sig).unwrap();
+    let value = Value::parse(" [ 0x10 ,2 ] ", &sig("ay")).unwrap();
+    assert_eq!(value.to_string(), "[16, 2]");
+    let value = Value::parse("@as ['a']", &sig("as")).unwrap();
+    assert_eq!(value.to_string(), "['a']");
+    let value = Value::parse("[{'a', 1}]", &sig("a{si}")).unwrap();
+    assert_eq!(value.to_string(), "{'a': 1}");
+    assert_eq!(
+        Value::parse("nothing", &sig("u")).unwrap().get_inner(),
+        None
+    );
+
+    let error = |sig: &'static str, text: &str| {
+        Value::parse(text, &Signature::from_static_str(sig).unwrap())
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error("y", "256"),
+        "256 is out of range for type `y` at position 0"
+    );
+    assert_eq!(
+        error("(ii)", "(1, 'a')"),
+        "expected an integer at position 4"
+    );
+    assert_eq!(error("as", "['a' 'b']"), "expected `,` at position 5");
+    assert_eq!(error("s", "'a"), "unterminated string at position 0");
+    assert_eq!(
+        error("as", "@ai []"),
+        "type annotation `ai` does not match the expected type `as` at position 0"
+    );
+    assert_eq!(
+        error("v", "<[]>"),
+        "cannot infer the type of an empty array, annotate it like `@as []` at position 3"
+    );
+    assert_eq!(error("u", "1 2"), "unexpected trailing input at position 2");
+    assert_eq!(
+        error("v", "<@ 1>"),
+        "expected a type after `@` at position 1"
+    );
+    assert_eq!(error("u", "@"), "expected a type after `@` at position 0");
+    assert_eq!(
+        error("a{sv}", "{'a': <@mi nothing>}"),
+        "maybe values are not supported inside dictionaries at position 11"
+    );
+    assert_eq!(
+        error("a{sms}", "{'a': just 'b'}"),
+        "maybe values are not supported inside dictionaries at position 6"
+    );
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_value_json() {
+    use gludconfig::value::Value;
+    use serde_json::json;
+    use zvariant::Signature;
+
+    let round_trip = |sig: &str, json: serde_json::Value| {
+        let sig = Signature::try_from(sig).unwrap();
+        let value = Value::from_json(&json, &sig).unwrap();
+        assert_eq!(value.signature(), sig);
+        assert_eq!(value.to_json(), json);
+    };
+
+    round_trip("u", json!(42));
+    round_trip("t", json!(u64::MAX));
+    round_trip("d", json!(1.5));
+    round_trip("s", json!("foo"));
+    round_trip("(isb)", json!([1, "a", true]));
+    round_trip("aas", json!([["a"], []]));
+    round_trip("a{sv}", json!({"k": 1, "l": [true, "x"], "m": {"n": 2.5}}));
+    round_trip("a{u(sd)}", json!({"1": ["a", 0.5]}));
+    round_trip("ms", json!("a"));
+    round_trip("ms", json!(null));
+    round_trip("u", json!(null));
+
+    let value = Value::from_json(&json!({"k": 1}), &Signature::try_from("a{sv}").unwrap()).unwrap();
+    assert_eq!(value.to_string(), "{'k': <1>}");
+    assert_eq!(
+        Value::from_json(&json!(null), &Signature::try_from("u").unwrap())
+            .unwrap()
+            .get_inner(),
+        None
+    );
+
+    let error = |sig: &str, json: serde_json::Value| {
+        Value::from_json(&json, &Signature::try_from(sig).unwrap())
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error("y", json!(256)),
+        "256 is out of range for type `y` at $"
+    );
+    assert_eq!(
+        error("ai", json!([1, "a"])),
+        "expected an integer, found `\"a\"` at $[1]"
+    );
+    assert_eq!(
+        error("a{sd}", json!({"k": true})),
+        "expected a number, found `true` at $.k"
+    );
+    assert_eq!(
+        error("(ii)", json!([1])),
+        "expected a tuple of type `(ii)`, found `[1]` at $"
+    );
+    assert_eq!(
+        error("i", json!(1.5)),
+        "expected an integer, found `1.5` at $"
+    );
+    assert_eq!(
+        error("a{sms}", json!({"k": "a"})),
+        "maybe values are not supported inside dictionaries at $"
+    );
+}
+
+#[cfg(all(feature = "tests", feature = "definition"))]
+#[test]
+fn test_schema_definition() {
+    use gludconfig::definition::{Format, SchemaDefinition};
+
+    let toml = r#"
+        name = "org.foo.display"
+        version = 1
+
+        [[properties]]
+        name = "scale"
+        signature = "d"
+        default = 1.0
+        choices = [1.0, 2.0]
+        about = "Scale factor"
+        about_l10n = { de = "Skalierungsfaktor" }
+        widget = "slider"
+
+        [[properties]]
+        name = "outputs"
+        signature = "a{s(ib)}"
+        default = { "eDP-1" = [1, true] }
+
+        [[triggers]]
+        name = "reload"
+        signature = "s"
+    "#;
+    let schema = SchemaDefinition::parse(toml, Format::Toml)
+        .unwrap()
+        .into_schema()
+        .unwrap();
+    assert_eq!(schema.name(), "org.foo.display");
+    let scale = schema.resolve("scale").unwrap();
+    assert_eq!(scale.about_localized("de_DE"), "Skalierungsfaktor");
+    assert_eq!(scale.widget().as_str(), "slider");
+    assert_eq!(
+        schema.resolve("outputs").unwrap().get_value().cloned(),
+        gludconfig::value::Value::parse("{'eDP-1': (1, true)}", &"a{s(ib)}".try_into().unwrap())
+            .unwrap()
+            .get_inner()
+            .cloned()
+    );
+    assert_eq!(schema.triggers().next().unwrap().signature(), "s");
+
+    let json = r#"{"name": "org.foo.json", "version": 1, "properties": [{"name": "a", "signature": "u", "default": 5}]}"#;
+    let schema = SchemaDefinition::parse(json, Format::Json)
+        .unwrap()
+        .into_schema()
+        .unwrap();
+    assert_eq!(schema.properties().count(), 1);
+
+    let error = |text: &str| {
+        SchemaDefinition::parse(text, Format::Toml)
+            .and_then(SchemaDefinition::into_schema)
+            .unwrap_err()
+    };
+    let err = error(
+        "name = \"a\"\nversion = 1\n[[properties]]\nname = \"a\"\nsignature = \"y\"\ndefault = 300",
+    );
+    assert_eq!(
+        format!("{:#}", err),
+        "In property a: While reading the default: 300 is out of range for type `y` at $"
+    );
+    let err = error("name = \"a\"\nversion = 1\ncolour = 1");
+    assert!(format!("{:#}", err).contains("unknown field `colour`"));
+    let err = error(
+        "name = \"a\"\nversion = 1\n[[properties]]\nname = \"a\"\nsignature = \"mi\"\ndefault = 1",
+    );
+    assert_eq!(
+        format!("{:#}", err),
+        "In property a: Invalid signature `mi`"
+    );
+    let err = error(
+        "name = \"a\"\nversion = 1\n[[properties]]\nname = \"a\"\nsignature = \"u\"\ndefault = 1\n[[properties]]\nname = \"a\"\nsignature = \"s\"\ndefault = \"\"",
+    );
+    assert_eq!(
+        format!("{:#}", err),
+        "Property `a` is defined more than once"
+    );
+    let err = error(
+        "name = \"a\"\nversion = 1\n[[triggers]]\nname = \"t\"\nsignature = \"s\"\n[[triggers]]\nname = \"t\"\nsignature = \"u\"",
+    );
+    assert_eq!(
+        format!("{:#}", err),
+        "Trigger `t` is defined more than once"
+    );
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_keyfile() {
+    use gludconfig::keyfile::KeyFile;
+
+    let text = "# dumped settings\n[org.foo.display]\nscale=2.0\noutputs = {'x': <1>}\n\n[org.foo.foo]\nwith_choices=9\n";
+    let keyfile = KeyFile::parse(text).unwrap();
+    assert_eq!(keyfile.groups.len(), 2);
+    assert_eq!(keyfile.groups[0].name, "org.foo.display");
+    assert_eq!(keyfile.groups[0].entries[1].key, "outputs");
+    assert_eq!(keyfile.groups[0].entries[1].value, "{'x': <1>}");
+    assert_eq!(keyfile.groups[0].entries[1].line, 4);
+    assert_eq!(keyfile.groups[1].line, 6);
+    assert_eq!(
+        keyfile.to_string(),
+        "[org.foo.display]\nscale=2.0\noutputs={'x': <1>}\n\n[org.foo.foo]\nwith_choices=9\n"
+    );
+    let reparsed = KeyFile::parse(&keyfile.to_string()).unwrap();
+    assert_eq!(reparsed.to_string(), keyfile.to_string());
+
+    let error = |text: &str| KeyFile::parse(text).unwrap_err().to_string();
+    assert_eq!(
+        error("a=1"),
+        "Line 1: key outside of a `[schema.name]` group"
+    );
+    assert_eq!(error("[a]\nb=1\nc\n"), "Line 3: expected `key=value`");
+    assert_eq!(
+        error("[a]\nb=1\nb=2\n"),
+        "Line 3: key `b` appears twice in its group"
+    );
+    assert_eq!(error("[a\n"), "Line 1: expected `[schema.name]`");
+}
+
+#[cfg(feature = "tests")]
+#[test]
+fn test_schema_writes() {
+    use gludconfig::{property::Property, schema::Schema, value::Value};
+
+    let mut schema = Schema::builder()
+        .name("org.foo.display".to_string())
+        .version(1)
+        .property(
+            Property::builder()
+                .name("scale".to_string())
+                .signature(<f64 as zvariant::Type>::signature())
+                .default(Value::wrap(Some(2.0)))
+                .build()
+                .unwrap(),
+        )
+        .property(
+            Property::builder()
+                .name("zoom".to_string())
+                .signature(<f64 as zvariant::Type>::signature())
+                .deprecated("Use scale".to_string())
+                .replaced_by("scale".to_string())
+                .build()
+                .unwrap(),
+        )
+        .property(
+            Property::builder()
+                .name("double_scale".to_string())
+                .signature(<f64 as zvariant::Type>::signature())
+                .computed("scale * 2".to_string())
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+
+    let four = Value::wrap(Some(4.0));
+    assert_eq!(schema.set("zoom", four.clone().into()).unwrap(), "scale");
+    assert_eq!(schema.recompute(), vec!["double_scale"]);
+    assert!(schema.set("nope", four.clone().into()).is_err());
+    assert!(schema.set("scale", Value::wrap(Some(1u32)).into()).is_err());
+    let values = schema.values();
+    assert_eq!(values["zoom"], four.clone().into());
+    assert_eq!(values["double_scale"], Value::wrap(Some(8.0)).into());
+
+    let info = schema.property_info("zoom", None).unwrap();
+    assert_eq!(info.name, "scale");
+    assert_eq!(info.deprecated, true);
+    assert_eq!(info.current, four.into());
+
+    let mut schema = Schema::builder()
+        .name("org.foo.display".to_string())
+        .version(1)
+        .property(
+            Property::builder()
+                .name("scale".to_string())
+                .signature(<f64 as zvariant::Type>::signature())
+                .default(Value::wrap(Some(2.0)))
+                .value(Value::wrap(Some(3.0)))
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(schema.reset_all(), (true, vec!["scale".to_string()]));
+    assert_eq!(
+        schema.resolve("scale").unwrap().get_value().cloned(),
+        Some(zvariant::Value::from(2.0).into())
+    );
+}
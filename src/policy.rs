@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use homedir::get_my_home;
+
+use crate::{definition::toml_table_to_json, error::DefinitionError};
+
+/// What a caller may do with a schema
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    None,
+    Read,
+    #[default]
+    Write,
+    /// Write, and also delete schemas registered by other applications, see `Owner`
+    Admin,
+}
+
+impl Access {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Access::None => "none",
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Admin => "admin",
+        }
+    }
+}
+
+/// Who may read and write which schemas, read by the daemon from
+/// `~/.config/gludconfig/policy.toml` when it starts. Without the file everyone may write.
+/// `admin` is only granted by the file, or to root where no rule matches.
+///
+/// ```toml
+/// # For callers no rule matches, `write` if left out
+/// default = "read"
+///
+/// [[rules]]
+/// schema = "org.foo.security"
+/// executable = "/usr/bin/foo-settings"
+/// access = "write"
+/// ```
+///
+/// `schema` matches the schema of that name and every schema below it, `org.foo` matches
+/// `org.foo.bar` but not `org.foobar`, and an empty one matches every schema. `executable` and
+/// `uid` are optional. The most specific matching rule wins: the one with the longest schema
+/// prefix, then the one naming more of `executable` and `uid`, then the last one.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    default: Access,
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    #[serde(default)]
+    schema: String,
+    executable: Option<String>,
+    uid: Option<u32>,
+    access: Access,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            default: Access::Write,
+            rules: vec![],
+        }
+    }
+}
+
+/// The peer credentials of a caller, as far as the bus could tell them
+#[derive(Debug, Default, Clone)]
+pub struct Caller {
+    pub uid: Option<u32>,
+    pub pid: Option<u32>,
+    pub executable: Option<String>,
+    /// The Flatpak app id, for sandboxed callers
+    pub app_id: Option<String>,
+}
+
+/// The application that registered a schema. Only it may register the schema again or delete
+/// it, unless an admin overrides that. Without app id and executable the registering caller
+/// could not be identified, and no one but an admin may change the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub app_id: Option<String>,
+    pub executable: Option<String>,
+}
+
+impl Owner {
+    /// The owner a schema registered by `caller` gets
+    pub fn of(caller: &Caller) -> Self {
+        Self {
+            app_id: caller.app_id.clone(),
+            executable: caller.executable.clone(),
+        }
+    }
+
+    /// Compares app ids if the owner has one, executables otherwise. An unknown owner is no one.
+    pub fn is(&self, caller: &Caller) -> bool {
+        match (&self.app_id, &self.executable) {
+            (Some(app_id), _) => caller.app_id.as_ref() == Some(app_id),
+            (None, Some(executable)) => caller.executable.as_ref() == Some(executable),
+            (None, None) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.app_id, &self.executable) {
+            (Some(app_id), _) => write!(f, "{}", app_id),
+            (None, Some(executable)) => write!(f, "{}", executable),
+            (None, None) => write!(f, "an unknown application"),
+        }
+    }
+}
+
+impl Policy {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let document = text
+            .parse::<toml_edit::Document>()
+            .map_err(|err| DefinitionError::InvalidToml(err.to_string()))?;
+        Ok(serde_json::from_value(toml_table_to_json(
+            document.as_table().iter(),
+        )?)?)
+    }
+
+    pub fn path() -> anyhow::Result<PathBuf> {
+        let home =
+            get_my_home()?.ok_or(anyhow::anyhow!("Failed to fetch home directory for user"))?;
+        Ok(home.join(".config/gludconfig/policy.toml"))
+    }
+
+    /// Reads the policy at `path`, or the default policy if there is no file
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|err| err.context(format!("While loading {}", path.display()))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => {
+                Err(anyhow::Error::new(err).context(format!("While loading {}", path.display())))
+            }
+        }
+    }
+
+    /// Whether every caller has at least `access`, so that there is no need to look them up
+    pub fn allows_everyone(&self, access: Access) -> bool {
+        self.default >= access && self.rules.iter().all(|rule| rule.access >= access)
+    }
+
+    /// Where no rule matches, root is an admin and everyone else gets the default
+    pub fn access(&self, caller: &Caller, schema: &str) -> Access {
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(caller, schema))
+            .max_by_key(|rule| {
+                (
+                    rule.schema.trim_end_matches('.').len(),
+                    rule.executable.is_some() as u8 + rule.uid.is_some() as u8,
+                )
+            })
+            .map(|rule| rule.access)
+            .unwrap_or(match caller.uid {
+                Some(0) => Access::Admin,
+                _ => self.default,
+            })
+    }
+}
+
+impl Rule {
+    fn matches(&self, caller: &Caller, schema: &str) -> bool {
+        let prefix = self.schema.trim_end_matches('.');
+        let schema_matches = prefix.is_empty()
+            || schema
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+        let executable_matches = self.executable.is_none() || caller.executable == self.executable;
+        let uid_matches = self.uid.is_none() || caller.uid == self.uid;
+        schema_matches && executable_matches && uid_matches
+    }
+}
@@ -1,10 +1,12 @@
-use std::ops::Deref;
+use std::{collections::BTreeMap, ops::Deref};
 
 use zvariant::{OwnedSignature, OwnedValue, Signature};
 
 use crate::{
     builder_get,
     error::{BuilderError, PropertyError, ValueError},
+    expr::Expr,
+    locale::{fallback_chain, normalize, Catalog, Translation},
     value::{Nullable, Value},
 };
 
@@ -19,6 +21,154 @@ pub struct Property {
     show_in_settings: bool,
     writable: bool,
     sig: OwnedSignature,
+    group: String,
+    order: i32,
+    widget: WidgetHint,
+    advanced: bool,
+    translations: BTreeMap<String, Translation>,
+    deprecated: bool,
+    deprecation: String,
+    replaced_by: String,
+    enabled_when: String,
+    reject_when_disabled: bool,
+    computed: String,
+}
+
+/// Description of a property as returned by `metadata_v2` of `org.glud.GludConfig.Property`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, zvariant::Type, zvariant::Value)]
+pub struct PropertyInfo {
+    pub writable: bool,
+    pub name: String,
+    pub description: String,
+    pub summary: String,
+    pub show_in_settings: bool,
+    pub signature: OwnedSignature,
+    pub current: Nullable,
+    pub group: String,
+    pub order: i32,
+    pub widget: String,
+    pub advanced: bool,
+    pub deprecated: bool,
+    pub deprecation: String,
+    pub replaced_by: String,
+    pub enabled: bool,
+    pub enabled_when: String,
+    pub computed: String,
+    pub default: Nullable,
+    pub choices: Vec<Nullable>,
+}
+
+/// The reply of `metadata` as it was before `metadata_v2`, kept so older clients keep working
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, zvariant::Type, zvariant::Value)]
+pub struct PropertyInfoV0 {
+    pub writable: bool,
+    pub name: String,
+    pub description: String,
+    pub summary: String,
+    pub show_in_settings: bool,
+    pub signature: OwnedSignature,
+    pub current: Nullable,
+}
+
+impl From<PropertyInfo> for PropertyInfoV0 {
+    fn from(value: PropertyInfo) -> Self {
+        PropertyInfoV0 {
+            writable: value.writable,
+            name: value.name,
+            description: value.description,
+            summary: value.summary,
+            show_in_settings: value.show_in_settings,
+            signature: value.signature,
+            current: value.current,
+        }
+    }
+}
+
+impl PropertyInfo {
+    /// Same as `PropertyInfo::from`, with `summary` and `description` translated to `locale`
+    pub fn localized(property: Property, locale: &str) -> Self {
+        let description = property.long_about_localized(locale).to_string();
+        let summary = property.about_localized(locale).to_string();
+        PropertyInfo {
+            description,
+            summary,
+            ..property.into()
+        }
+    }
+}
+
+impl From<Property> for PropertyInfo {
+    fn from(value: Property) -> Self {
+        PropertyInfo {
+            name: value.name().to_string(),
+            writable: value.is_writable(),
+            description: value.long_about().to_string(),
+            summary: value.about().to_string(),
+            show_in_settings: value.show_in_settings(),
+            signature: value.signature().into(),
+            group: value.group().to_string(),
+            order: value.order(),
+            widget: value.widget().to_string(),
+            advanced: value.is_advanced(),
+            deprecated: value.is_deprecated(),
+            deprecation: value.deprecation().to_string(),
+            replaced_by: value.replaced_by().unwrap_or_default().to_string(),
+            // Depends on the other properties of the schema, see `Schema::is_enabled`
+            enabled: true,
+            enabled_when: value.enabled_when().unwrap_or_default().to_string(),
+            computed: value.computed().unwrap_or_default().to_string(),
+            default: value.default.clone().into(),
+            choices: value.choices.iter().cloned().map(Into::into).collect(),
+            current: <Property as Into<Value>>::into(value).into(),
+        }
+    }
+}
+
+/// Hint for settings frontends on which widget should be used to edit a property.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, zvariant::Type,
+)]
+pub enum WidgetHint {
+    /// Let the frontend pick a widget based on the property's signature
+    #[default]
+    Auto = 0,
+    Switch = 1,
+    Slider = 2,
+    FileChooser = 3,
+    Color = 4,
+}
+
+impl WidgetHint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WidgetHint::Auto => "auto",
+            WidgetHint::Switch => "switch",
+            WidgetHint::Slider => "slider",
+            WidgetHint::FileChooser => "file_chooser",
+            WidgetHint::Color => "color",
+        }
+    }
+}
+
+impl std::str::FromStr for WidgetHint {
+    type Err = PropertyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(WidgetHint::Auto),
+            "switch" => Ok(WidgetHint::Switch),
+            "slider" => Ok(WidgetHint::Slider),
+            "file_chooser" => Ok(WidgetHint::FileChooser),
+            "color" => Ok(WidgetHint::Color),
+            _ => Err(PropertyError::UnknownWidget),
+        }
+    }
+}
+
+impl std::fmt::Display for WidgetHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl PartialEq for Property {
@@ -55,6 +205,10 @@ impl Property {
         self.current.get_inner()
     }
 
+    pub fn get_default(&self) -> Option<&OwnedValue> {
+        self.default.get_inner()
+    }
+
     pub fn set_value(&mut self, value: Value) -> anyhow::Result<()> {
         if !self.writable {
             return Err(
@@ -94,6 +248,57 @@ impl Property {
         &self.long_about
     }
 
+    /// Returns the `about` text for `locale`, falling back to the language without region and then to english
+    pub fn about_localized(&self, locale: &str) -> &str {
+        self.translation(locale, |t| &t.about)
+            .unwrap_or(&self.about)
+    }
+
+    /// Returns the `long_about` text for `locale`, falling back to the language without region and then to english
+    pub fn long_about_localized(&self, locale: &str) -> &str {
+        self.translation(locale, |t| &t.long_about)
+            .unwrap_or(&self.long_about)
+    }
+
+    fn translation<'a>(
+        &'a self,
+        locale: &str,
+        field: impl Fn(&'a Translation) -> &'a String,
+    ) -> Option<&'a str> {
+        fallback_chain(locale)
+            .iter()
+            .filter_map(|locale| self.translations.get(locale))
+            .map(field)
+            .find(|text| !text.is_empty())
+            .map(String::as_str)
+    }
+
+    pub fn translations(&self) -> &BTreeMap<String, Translation> {
+        &self.translations
+    }
+
+    /// Adds the translations found in `catalog` for `locale`, keeping already present ones if the catalog has no entry
+    pub fn translate(&mut self, locale: &str, catalog: &Catalog) {
+        let about = catalog
+            .about_for(&self.name, &self.about)
+            .map(str::to_string);
+        let long_about = catalog
+            .long_about_for(&self.name, &self.long_about)
+            .map(str::to_string);
+
+        if about.is_none() && long_about.is_none() {
+            return;
+        }
+
+        let translation = self.translations.entry(normalize(locale)).or_default();
+        if let Some(about) = about {
+            translation.about = about;
+        }
+        if let Some(long_about) = long_about {
+            translation.long_about = long_about;
+        }
+    }
+
     pub fn is_writable(&self) -> bool {
         self.writable
     }
@@ -101,6 +306,103 @@ impl Property {
     pub fn show_in_settings(&self) -> bool {
         self.show_in_settings
     }
+
+    /// The settings page section this property belongs to, empty if ungrouped
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Sort order of the property within its group, lower comes first
+    pub fn order(&self) -> i32 {
+        self.order
+    }
+
+    pub fn widget(&self) -> WidgetHint {
+        self.widget
+    }
+
+    /// Whether the property should be hidden behind an "advanced" toggle in settings
+    pub fn is_advanced(&self) -> bool {
+        self.advanced
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    /// The message explaining the deprecation, empty if the property is not deprecated
+    pub fn deprecation(&self) -> &str {
+        &self.deprecation
+    }
+
+    /// The key that reads and writes to this property are forwarded to, if any
+    pub fn replaced_by(&self) -> Option<&str> {
+        (!self.replaced_by.is_empty()).then_some(self.replaced_by.as_str())
+    }
+
+    /// The condition under which the property is enabled, see `gludconfig::expr` for the syntax
+    pub fn enabled_when(&self) -> Option<&str> {
+        (!self.enabled_when.is_empty()).then_some(self.enabled_when.as_str())
+    }
+
+    /// The expression the value of this property is computed from, computed properties are never writable
+    pub fn computed(&self) -> Option<&str> {
+        (!self.computed.is_empty()).then_some(self.computed.as_str())
+    }
+
+    /// Replaces the value of a computed property, bypassing `writable`
+    pub(crate) fn set_computed(&mut self, value: Value) -> bool {
+        let changed = self.current != value;
+        self.current = value;
+        changed
+    }
+
+    /// Whether writes should be rejected while the property is disabled
+    pub fn rejects_when_disabled(&self) -> bool {
+        self.reject_when_disabled
+    }
+}
+
+/// The layout of `Property` before group, order, widget and the later fields were added. Only
+/// read back from old databases, see `Storage::decode_schema`.
+#[derive(serde::Deserialize, zvariant::Type)]
+pub(crate) struct PropertyV0 {
+    name: String,
+    about: String,
+    long_about: String,
+    default: Value,
+    current: Value,
+    choices: Vec<Value>,
+    show_in_settings: bool,
+    writable: bool,
+    sig: OwnedSignature,
+}
+
+impl From<PropertyV0> for Property {
+    fn from(value: PropertyV0) -> Self {
+        Property {
+            name: value.name,
+            about: value.about,
+            long_about: value.long_about,
+            default: value.default,
+            current: value.current,
+            choices: value.choices,
+            show_in_settings: value.show_in_settings,
+            writable: value.writable,
+            sig: value.sig,
+            group: String::new(),
+            order: 0,
+            widget: WidgetHint::default(),
+            advanced: false,
+            translations: BTreeMap::new(),
+            deprecated: false,
+            deprecation: String::new(),
+            replaced_by: String::new(),
+            enabled_when: String::new(),
+            reject_when_disabled: false,
+            computed: String::new(),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -114,6 +416,16 @@ pub struct PropertyBuilder {
     default: Option<Value>,
     choices: Vec<Value>,
     name: Option<String>,
+    group: Option<String>,
+    order: Option<i32>,
+    widget: Option<WidgetHint>,
+    advanced: Option<bool>,
+    translations: BTreeMap<String, Translation>,
+    deprecation: Option<String>,
+    replaced_by: Option<String>,
+    enabled_when: Option<String>,
+    reject_when_disabled: Option<bool>,
+    computed: Option<String>,
 }
 
 impl PropertyBuilder {
@@ -179,11 +491,77 @@ impl PropertyBuilder {
         self.signature = Some(signature);
         self
     }
+
+    pub fn group(mut self, group: String) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    pub fn order(mut self, order: i32) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn widget(mut self, widget: WidgetHint) -> Self {
+        self.widget = Some(widget);
+        self
+    }
+
+    pub fn advanced(mut self, advanced: bool) -> Self {
+        self.advanced = Some(advanced);
+        self
+    }
+
+    /// Marks the property as deprecated, `message` should tell users what to do instead
+    pub fn deprecated(mut self, message: String) -> Self {
+        self.deprecation = Some(message);
+        self
+    }
+
+    /// Forwards reads and writes to `key`, this implies the property is deprecated
+    pub fn replaced_by(mut self, key: String) -> Self {
+        self.replaced_by = Some(key);
+        self
+    }
+
+    /// Only enables the property while `condition` holds, e.g. `auto_suspend == true`
+    pub fn enabled_when(mut self, condition: String) -> Self {
+        self.enabled_when = Some(condition);
+        self
+    }
+
+    /// Computes the value from other properties of the schema, e.g. `scale * fractional_factor`.
+    /// This makes the property read only.
+    pub fn computed(mut self, expression: String) -> Self {
+        self.computed = Some(expression);
+        self
+    }
+
+    pub fn reject_when_disabled(mut self, reject_when_disabled: bool) -> Self {
+        self.reject_when_disabled = Some(reject_when_disabled);
+        self
+    }
+
+    pub fn about_translation(mut self, locale: String, about: String) -> Self {
+        self.translations
+            .entry(normalize(&locale))
+            .or_default()
+            .about = about;
+        self
+    }
+
+    pub fn long_about_translation(mut self, locale: String, long_about: String) -> Self {
+        self.translations
+            .entry(normalize(&locale))
+            .or_default()
+            .long_about = long_about;
+        self
+    }
 }
 
 impl PropertyBuilder {
     pub fn build(self) -> anyhow::Result<Property> {
-        let writable = self.writable.unwrap_or(true);
+        let writable = self.writable.unwrap_or(true) && self.computed.is_none();
         let show_in_settings = self.show_in_settings.unwrap_or(true);
         let signature = builder_get!(
             self,
@@ -230,6 +608,12 @@ impl PropertyBuilder {
             return Err(anyhow::Error::new(PropertyError::NotFoundInChoices).context(format!("The value of either `default` or `value` was not found in the provided choices")));
         }
 
+        if let Some(condition) = &self.enabled_when {
+            Expr::parse(condition).map_err(|err| {
+                anyhow::Error::new(err).context("Invalid `enabled_when` condition")
+            })?;
+        }
+
         Ok(Property {
             about: self.about.unwrap_or("No summary provided".to_string()),
             sig: signature.into(),
@@ -248,6 +632,17 @@ impl PropertyBuilder {
             choices: self.choices,
             show_in_settings: show_in_settings,
             writable: writable,
+            group: self.group.unwrap_or_default(),
+            order: self.order.unwrap_or(0),
+            widget: self.widget.unwrap_or_default(),
+            advanced: self.advanced.unwrap_or(false),
+            translations: self.translations,
+            deprecated: self.deprecation.is_some() || self.replaced_by.is_some(),
+            deprecation: self.deprecation.unwrap_or_default(),
+            replaced_by: self.replaced_by.unwrap_or_default(),
+            enabled_when: self.enabled_when.unwrap_or_default(),
+            reject_when_disabled: self.reject_when_disabled.unwrap_or(false),
+            computed: self.computed.unwrap_or_default(),
         })
     }
 }
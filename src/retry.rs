@@ -0,0 +1,20 @@
+//! Backoff for the generated `update_<property>` helpers, which retry `set_if` for as long as
+//! other clients keep changing the value in between
+
+use std::time::Duration;
+
+/// How long to wait before retry number `attempt`: doubling from 1ms up to 64ms, plus up to as
+/// much random jitter so that clients racing each other spread out
+pub fn delay(attempt: usize) -> Duration {
+    let base = 1000u64 << attempt.min(6);
+    let jitter = fastrand::u64(..base);
+    Duration::from_micros(base + jitter)
+}
+
+pub async fn backoff(attempt: usize) {
+    tokio::time::sleep(delay(attempt)).await
+}
+
+pub fn backoff_blocking(attempt: usize) {
+    std::thread::sleep(delay(attempt))
+}
@@ -1,6 +1,18 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::{builder_get, property::Property, trigger::Trigger, value::Value};
+use std::path::Path;
+
+use zvariant::OwnedValue;
+
+use crate::{
+    builder_get,
+    error::{ExprError, PropertyError, SchemaError},
+    expr::{Expr, Scalar},
+    locale::Catalog,
+    property::{Property, PropertyInfo, PropertyV0},
+    trigger::Trigger,
+    value::{Nullable, Value},
+};
 #[derive(Debug, serde::Serialize, serde::Deserialize, zvariant::Type)]
 pub struct Schema {
     name: String,
@@ -9,6 +21,26 @@ pub struct Schema {
     pub(crate) triggers: Vec<Trigger>,
 }
 
+/// The layout of `Schema` in databases written before `Storage` versioned its blobs
+#[derive(serde::Deserialize, zvariant::Type)]
+pub(crate) struct SchemaV0 {
+    name: String,
+    version: u32,
+    properties: Vec<PropertyV0>,
+    triggers: Vec<Trigger>,
+}
+
+impl From<SchemaV0> for Schema {
+    fn from(value: SchemaV0) -> Self {
+        Schema {
+            name: value.name,
+            version: value.version,
+            properties: value.properties.into_iter().map(Into::into).collect(),
+            triggers: value.triggers,
+        }
+    }
+}
+
 impl PartialEq for Schema {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
@@ -47,6 +79,356 @@ impl Schema {
     pub fn into_properties(self) -> impl Iterator<Item = Property> {
         self.properties.into_iter()
     }
+
+    /// Finds a property by key, following `replaced_by` of deprecated properties
+    pub fn resolve(&self, key: &str) -> Option<&Property> {
+        let mut property = self.properties().find(|p| p.name() == key)?;
+        // Cycles are rejected by `SchemaBuilder::build`, this only bounds the walk.
+        for _ in 0..self.properties.len() {
+            match property.replaced_by() {
+                Some(next) => property = self.properties().find(|p| p.name() == next)?,
+                None => return Some(property),
+            }
+        }
+        None
+    }
+
+    /// Same as `Schema::resolve`, but returns a mutable reference
+    pub fn resolve_mut(&mut self, key: &str) -> Option<&mut Property> {
+        let name = self.resolve(key)?.name().to_string();
+        self.properties_mut().find(|p| p.name() == name)
+    }
+
+    /// Checks that replacements exist and match, and that computed properties and `enabled_when`
+    /// conditions only reference existing properties without cycles. `SchemaBuilder::build` runs it.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for property in self.properties() {
+            let Some(replacement) = property.replaced_by() else {
+                continue;
+            };
+            let target = self.properties().find(|p| p.name() == replacement).ok_or(
+                SchemaError::ReplacementNotFound(
+                    property.name().to_string(),
+                    replacement.to_string(),
+                ),
+            )?;
+            if target.signature() != property.signature() {
+                return Err(SchemaError::ReplacementSignatureMismatch(
+                    property.name().to_string(),
+                    replacement.to_string(),
+                )
+                .into());
+            }
+            if self.resolve(property.name()).is_none() {
+                return Err(SchemaError::ReplacementCycle(property.name().to_string()).into());
+            }
+        }
+
+        for property in self.properties() {
+            let Some(expression) = property.computed() else {
+                continue;
+            };
+            for input in Expr::parse(expression)?.properties() {
+                if self.resolve(input).is_none() {
+                    return Err(SchemaError::UnknownInput(
+                        property.name().to_string(),
+                        input.to_string(),
+                    )
+                    .into());
+                }
+            }
+            if self
+                .computed_inputs(property.name())
+                .contains(property.name())
+            {
+                return Err(SchemaError::ComputedCycle(property.name().to_string()).into());
+            }
+        }
+
+        for property in self.properties() {
+            let Some(condition) = property.enabled_when() else {
+                continue;
+            };
+            for dependency in Expr::parse(condition)?.properties() {
+                if self.resolve(dependency).is_none() {
+                    return Err(SchemaError::UnknownDependency(
+                        property.name().to_string(),
+                        dependency.to_string(),
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The current value of `key` as seen by expressions
+    pub fn scalar(&self, key: &str) -> Result<Scalar, ExprError> {
+        let property = self
+            .resolve(key)
+            .ok_or(ExprError::UnknownProperty(key.to_string()))?;
+        Scalar::from_value(property.get_value())
+    }
+
+    /// Evaluates the `enabled_when` condition of `key`, properties without one are always enabled
+    pub fn is_enabled(&self, key: &str) -> anyhow::Result<bool> {
+        let Some(condition) = self.resolve(key).and_then(Property::enabled_when) else {
+            return Ok(true);
+        };
+        match Expr::parse(condition)?.eval(&|name| self.scalar(name))? {
+            Scalar::Bool(enabled) => Ok(enabled),
+            other => Err(ExprError::Type(format!(
+                "`enabled_when` of {} evaluated to {}, expected a bool",
+                key, other
+            ))
+            .into()),
+        }
+    }
+
+    /// Enabled state of every property that has an `enabled_when` condition.
+    /// Conditions that fail to evaluate count as enabled.
+    pub fn enabled_states(&self) -> BTreeMap<String, bool> {
+        self.properties()
+            .filter(|p| p.enabled_when().is_some())
+            .map(|p| {
+                (
+                    p.name().to_string(),
+                    self.is_enabled(p.name()).unwrap_or(true),
+                )
+            })
+            .collect()
+    }
+
+    /// Current value of every property, deprecated aliases having the value of their replacement
+    pub fn values(&self) -> BTreeMap<String, Nullable> {
+        self.properties()
+            .filter_map(|p| {
+                let value = self.resolve(p.name())?.get_value().cloned();
+                Some((p.name().to_string(), value.into()))
+            })
+            .collect()
+    }
+
+    /// Fails if `key` rejects writes while disabled and its condition currently is false
+    pub fn check_enabled(&self, key: &str) -> anyhow::Result<()> {
+        let rejects = self
+            .resolve(key)
+            .map(Property::rejects_when_disabled)
+            .unwrap_or(false);
+        if rejects && !self.is_enabled(key).unwrap_or(true) {
+            return Err(anyhow::Error::new(PropertyError::Disabled)
+                .context(format!("While trying to write to {}", key)));
+        }
+        Ok(())
+    }
+
+    /// Re-evaluates every computed property, returning the names of the ones whose value changed.
+    /// Expressions that fail to evaluate or convert set the property to null.
+    pub fn recompute(&mut self) -> Vec<String> {
+        let computed: Vec<(String, Expr)> = self
+            .properties()
+            .filter_map(|p| Some((p.name().to_string(), Expr::parse(p.computed()?).ok()?)))
+            .collect();
+
+        let mut changed = vec![];
+        // Computed properties may read each other, the build time cycle check guarantees this settles.
+        for _ in 0..=computed.len() {
+            let mut settled = true;
+            for (name, expr) in &computed {
+                let property = self.resolve(name).expect("computed property exists");
+                let value = expr
+                    .eval(&|name| self.scalar(name))
+                    .and_then(|scalar| scalar.into_value(&property.signature()))
+                    .ok()
+                    .flatten();
+                let value = Value::new(value, property.signature())
+                    .unwrap_or(Value::new::<u32>(None, property.signature()).unwrap());
+
+                let property = self.resolve_mut(name).expect("computed property exists");
+                if property.set_computed(value) {
+                    settled = false;
+                    if !changed.contains(name) {
+                        changed.push(name.clone());
+                    }
+                }
+            }
+            if settled {
+                break;
+            }
+        }
+        changed
+    }
+
+    /// Carries the values of `old`, an earlier definition of this schema, over to the properties
+    /// that still exist with the same signature. Values the new definition rejects, e.g. ones
+    /// no longer among the choices, are left at the new default.
+    pub fn keep_values(&mut self, old: &Schema) {
+        for property in self
+            .properties
+            .iter_mut()
+            .filter(|p| p.replaced_by().is_none() && p.computed().is_none())
+        {
+            let Some(previous) = old
+                .properties()
+                .find(|p| p.name() == property.name() && p.signature() == property.signature())
+            else {
+                continue;
+            };
+            if let Ok(value) = Value::new(previous.get_value().cloned(), property.signature()) {
+                let _ = property.set_value(value);
+            }
+        }
+        self.recompute();
+    }
+
+    /// Every property the value of `key` is transitively computed from
+    fn computed_inputs(&self, key: &str) -> BTreeSet<String> {
+        let mut inputs = BTreeSet::new();
+        let mut pending = vec![key.to_string()];
+        while let Some(key) = pending.pop() {
+            let Some(expr) = self
+                .resolve(&key)
+                .and_then(Property::computed)
+                .and_then(|expression| Expr::parse(expression).ok())
+            else {
+                continue;
+            };
+            for input in expr.properties() {
+                let input = self.resolve(input).map(Property::name).unwrap_or(input);
+                if inputs.insert(input.to_string()) {
+                    pending.push(input.to_string());
+                }
+            }
+        }
+        inputs
+    }
+
+    /// Returns `key` along with every deprecated alias that is forwarded to it
+    pub fn keys_of(&self, key: &str) -> Vec<String> {
+        self.properties()
+            .filter(|p| p.name() == key || self.resolve(p.name()).map(Property::name) == Some(key))
+            .map(|p| p.name().to_string())
+            .collect()
+    }
+
+    /// Writes `value` to `key`, following deprecated aliases. Returns the name of the property
+    /// that was written to. Computed properties are not updated, see `Schema::recompute`.
+    pub fn set(&mut self, key: &str, value: Nullable) -> anyhow::Result<String> {
+        self.check_enabled(key)?;
+        let not_found = SchemaError::PropertyNotFound(self.name.clone(), key.to_string());
+        let property = self.resolve_mut(key).ok_or(not_found)?;
+        let value = Value::new::<OwnedValue>(value.into(), property.signature())?;
+        property.set_value(value)?;
+        Ok(property.name().to_string())
+    }
+
+    /// Resets every writable property to its default. Returns whether all of them could be reset,
+    /// along with the names of the properties that changed, computed ones included.
+    pub fn reset_all(&mut self) -> (bool, Vec<String>) {
+        let mut res = true;
+        let mut was_reset = vec![];
+        // Deprecated aliases are forwarded to their replacement, which is reset on its own.
+        // Computed properties are never writable and follow their inputs instead.
+        for p in self
+            .properties_mut()
+            .filter(|p| p.replaced_by().is_none() && p.computed().is_none())
+        {
+            if Property::reset(p) {
+                was_reset.push(p.name().to_string());
+            } else {
+                res = false;
+            }
+        }
+        for key in self.recompute() {
+            if !was_reset.contains(&key) {
+                was_reset.push(key);
+            }
+        }
+        (res, was_reset)
+    }
+
+    /// Builds the info for `key`. For deprecated aliases the info of the replacement is returned,
+    /// with the deprecation fields of the alias.
+    pub fn property_info(self, key: &str, locale: Option<&str>) -> anyhow::Result<PropertyInfo> {
+        let not_found = || SchemaError::PropertyNotFound(self.name.clone(), key.to_string());
+        let alias = self
+            .properties()
+            .find(|p| p.name() == key)
+            .ok_or_else(not_found)?;
+        let (deprecated, deprecation, replaced_by) = (
+            alias.is_deprecated(),
+            alias.deprecation().to_string(),
+            alias.replaced_by().unwrap_or_default().to_string(),
+        );
+        let resolved = self.resolve(key).ok_or_else(not_found)?.name().to_string();
+        let enabled = self.is_enabled(&resolved).unwrap_or(true);
+
+        let property = self
+            .into_properties()
+            .find(|p| p.name() == resolved)
+            .expect("resolved property exists");
+
+        let info = match locale {
+            Some(locale) => PropertyInfo::localized(property, locale),
+            None => property.into(),
+        };
+        Ok(PropertyInfo {
+            deprecated,
+            deprecation,
+            replaced_by,
+            enabled,
+            ..info
+        })
+    }
+
+    /// Applies a translation catalog for `locale` to every property of the schema
+    pub fn translate(&mut self, locale: &str, catalog: &Catalog) {
+        self.properties_mut()
+            .for_each(|property| property.translate(locale, catalog));
+    }
+
+    /// Loads every catalog found in `dir` and applies it.
+    ///
+    /// Both flat (`<dir>/de.po`, `<dir>/de.ftl`) and gettext style (`<dir>/de/LC_MESSAGES/<domain>.mo`)
+    /// layouts are supported, the locale being taken from the file stem or the directory name respectively.
+    /// Only the `<domain>.mo` and `<domain>.po` catalogs of the gettext layout are read, other
+    /// applications may install theirs next to them.
+    pub fn load_translations(&mut self, dir: impl AsRef<Path>, domain: &str) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                let locale = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                let messages = path.join("LC_MESSAGES");
+                for extension in ["mo", "po"] {
+                    let path = messages.join(format!("{}.{}", domain, extension));
+                    if path.is_file() {
+                        self.translate(&locale, &Catalog::load(&path)?);
+                    }
+                }
+            } else if is_catalog(&path) {
+                let locale = path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                self.translate(&locale, &Catalog::load(&path)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_catalog(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("po" | "mo" | "ftl")
+    )
 }
 
 #[derive(Default)]
@@ -114,12 +496,16 @@ impl SchemaBuilder {
             format!("Missing Property: version: Please use `SchemaBuilder::version` to set it")
         );
 
-        Ok(Schema {
+        let mut schema = Schema {
             version,
             triggers: self.triggers,
             name: name,
             properties: self.properties,
-        })
+        };
+
+        schema.validate()?;
+        schema.recompute();
+        Ok(schema)
     }
 }
 
@@ -0,0 +1,112 @@
+use crate::{
+    error::{PropertyError, SchemaError},
+    schema::Schema,
+    value::Nullable,
+};
+
+/// Writes a client staged in delayed-apply mode. They are validated when staged, but only written
+/// to storage once the client applies them, on top of whatever the schema holds by then.
+#[derive(Debug, Default, Clone)]
+pub struct Session {
+    writes: Vec<Write>,
+}
+
+#[derive(Debug, Clone)]
+enum Write {
+    Set(String, Nullable),
+    Reset(String),
+}
+
+impl Session {
+    /// Stages writing `value` to `key`, fails if it would fail on top of the staged writes
+    pub fn set(&mut self, schema: Schema, key: &str, value: Nullable) -> anyhow::Result<()> {
+        self.stage(schema, vec![Write::Set(key.to_string(), value)])
+    }
+
+    /// Stages writing several values, either all of them or, if one fails, none
+    pub fn set_many(
+        &mut self,
+        schema: Schema,
+        values: Vec<(String, Nullable)>,
+    ) -> anyhow::Result<()> {
+        let writes = values
+            .into_iter()
+            .map(|(key, value)| Write::Set(key, value))
+            .collect();
+        self.stage(schema, writes)
+    }
+
+    /// Stages resetting `key` to its default. Like `Property::reset`, returns false and stages
+    /// nothing if the property is not writable.
+    pub fn reset(&mut self, mut schema: Schema, key: &str) -> anyhow::Result<bool> {
+        self.preview(&mut schema);
+        let not_found = SchemaError::PropertyNotFound(schema.name().to_string(), key.to_string());
+        let property = schema.resolve_mut(key).ok_or(not_found)?;
+        if !property.reset() {
+            return Ok(false);
+        }
+        self.writes.push(Write::Reset(key.to_string()));
+        Ok(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    fn stage(&mut self, mut schema: Schema, writes: Vec<Write>) -> anyhow::Result<()> {
+        self.preview(&mut schema);
+        for write in &writes {
+            write.apply(&mut schema)?;
+        }
+        self.writes.extend(writes);
+        Ok(())
+    }
+
+    /// Applies the staged writes to `schema`. Returns the names of the properties that were
+    /// written, followed by the computed ones that changed along with them.
+    pub fn apply(&self, schema: &mut Schema) -> anyhow::Result<Vec<String>> {
+        let mut changed = vec![];
+        for write in &self.writes {
+            let key = write.apply(schema)?;
+            if !changed.contains(&key) {
+                changed.push(key);
+            }
+        }
+        for key in schema.recompute() {
+            if !changed.contains(&key) {
+                changed.push(key);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Applies the staged writes to `schema` for reads, skipping those that stopped applying
+    /// since they were staged, e.g. because the property got disabled. `apply` still fails on
+    /// them, so the conflict is only reported once the client applies.
+    pub fn preview(&self, schema: &mut Schema) {
+        for write in &self.writes {
+            // A failed write leaves the schema as it was.
+            let _ = write.apply(schema);
+        }
+        schema.recompute();
+    }
+}
+
+impl Write {
+    /// Returns the name of the property that was written, aliases being resolved
+    fn apply(&self, schema: &mut Schema) -> anyhow::Result<String> {
+        match self {
+            Write::Set(key, value) => schema.set(key, value.clone()),
+            Write::Reset(key) => {
+                let not_found =
+                    SchemaError::PropertyNotFound(schema.name().to_string(), key.clone());
+                let property = schema.resolve_mut(key).ok_or(not_found)?;
+                if !property.reset() {
+                    return Err(anyhow::Error::new(PropertyError::NotWritable)
+                        .context(format!("While trying to reset {}", key)));
+                }
+                Ok(property.name().to_string())
+            }
+        }
+    }
+}
@@ -1,22 +1,71 @@
 use std::{
     collections::BTreeMap,
-    fmt::Display,
     ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 
+use anyhow::Context;
 use homedir::get_my_home;
 use zvariant::{from_slice, to_bytes};
 
 use crate::{
+    error::{DaemonError, DaemonResult, StorageError},
     impls::rustqlite::RustQliteImpl,
+    policy::Owner,
     property::Property,
-    schema::{self, Schema},
+    schema::{Schema, SchemaV0},
     storage_backend::StorageBackend,
 };
 
 pub struct Storage {
     path: String,
     conn: RustQliteImpl,
+    /// One lock per schema name, see `Storage::lock_schema`
+    schema_locks: Arc<SchemaLocks>,
+    /// The last serial handed out by `Storage::next_serials`
+    serial: AtomicU64,
+}
+
+/// Name of the counter `Storage::next_serials` records the last serial in
+const SERIAL_COUNTER: &str = "serial";
+
+type SchemaLocks = Mutex<BTreeMap<String, Arc<tokio::sync::Mutex<()>>>>;
+
+/// Held while a schema is read, changed and written back, see `Storage::lock_schema`
+pub struct SchemaGuard {
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+    name: String,
+    locks: Arc<SchemaLocks>,
+}
+
+impl Drop for SchemaGuard {
+    /// Removes the lock of the schema once no one holds or waits for it, so that locks taken for
+    /// any name a client sends do not pile up
+    fn drop(&mut self) {
+        let mut locks = self.locks.lock().unwrap();
+        self.guard.take();
+        // Left is the reference of the map, unless someone cloned the lock to wait for it.
+        if locks
+            .get(&self.name)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.name);
+        }
+    }
+}
+
+/// Stored schemas start with `FORMAT_MAGIC` followed by the format version as a little endian
+/// `u32`, see `Storage::encode_schema`. Blobs without it were written before the format was
+/// versioned and have the layout of `SchemaV0`.
+const FORMAT_MAGIC: &[u8; 4] = b"GLUD";
+const FORMAT_VERSION: u32 = 1;
+
+/// Released when dropped, see `Storage::lock`
+pub struct StorageLock {
+    _file: std::fs::File,
 }
 
 impl Deref for Storage {
@@ -43,9 +92,33 @@ impl Storage {
                 .to_string()
         );
 
+        Self::connect(path).await
+    }
+
+    /// Opens an existing database somewhere else read only, e.g. a copy of another user's
+    /// `data.db` to compare against. Writes through it fail.
+    pub async fn open(path: impl Into<String>) -> anyhow::Result<Storage> {
+        let path = path.into();
+        let conn = RustQliteImpl::connect_read_only(&path)
+            .await
+            .with_context(|| format!("While opening {}", path))?;
+        Ok(Storage {
+            conn,
+            path,
+            schema_locks: Arc::default(),
+            // Serials are only handed out by the daemon, which never opens databases read only
+            serial: AtomicU64::new(0),
+        })
+    }
+
+    async fn connect(path: String) -> anyhow::Result<Storage> {
+        let conn = RustQliteImpl::connect(&path).await?;
+        let serial = conn.fetch_counter(SERIAL_COUNTER.to_string()).await?;
         Ok(Storage {
-            conn: RustQliteImpl::connect(&path).await?,
+            conn,
             path,
+            schema_locks: Arc::default(),
+            serial: AtomicU64::new(serial),
         })
     }
 
@@ -53,47 +126,137 @@ impl Storage {
         &self.path
     }
 
-    pub async fn get_schema(&self, schema: String) -> zbus::fdo::Result<Schema> {
+    /// Takes an exclusive lock on `data.db.lock`, next to the database. The daemon holds it for as
+    /// long as it runs, offline tools while they write, so the two never race each other.
+    /// Without `wait`, fails right away if the lock is taken.
+    pub fn lock(&self, wait: bool) -> anyhow::Result<StorageLock> {
+        use nix::fcntl::{flock, FlockArg};
+        use std::os::fd::AsRawFd;
+
+        let path = format!("{}.lock", self.path);
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        let arg = match wait {
+            true => FlockArg::LockExclusive,
+            false => FlockArg::LockExclusiveNonblock,
+        };
+        flock(file.as_raw_fd(), arg).map_err(|err| match err {
+            nix::errno::Errno::EWOULDBLOCK => StorageError::Locked(path).into(),
+            err => anyhow::Error::new(err).context(format!("While locking {}", path)),
+        })?;
+        Ok(StorageLock { _file: file })
+    }
+
+    /// Waits until no one else holds the lock of `name`. Every read-modify-write of a schema
+    /// happens under it, otherwise two concurrent writes to different keys would both write
+    /// back their own copy of the schema and one of them would be lost.
+    pub async fn lock_schema(&self, name: &str) -> SchemaGuard {
+        let lock = self
+            .schema_locks
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone();
+        SchemaGuard {
+            guard: Some(lock.lock_owned().await),
+            name: name.to_string(),
+            locks: self.schema_locks.clone(),
+        }
+    }
+
+    /// Reserves `count` consecutive serials for `property_changed_v2` and returns the first one.
+    /// They are recorded before being handed out, so serials keep growing across restarts.
+    pub async fn next_serials(&self, count: u64) -> DaemonResult<u64> {
+        let first = self.serial.fetch_add(count, Ordering::SeqCst) + 1;
+        self.conn
+            .raise_counter(SERIAL_COUNTER.to_string(), first + count - 1)
+            .await
+            .map_err(into_zbus_error)?;
+        Ok(first)
+    }
+
+    /// How many schema names currently have a lock, held or waited for
+    pub fn schema_lock_count(&self) -> usize {
+        self.schema_locks.lock().unwrap().len()
+    }
+
+    /// Encodes a schema the way it is kept in the database, with the current format version
+    pub fn encode_schema(schema: &Schema) -> anyhow::Result<Vec<u8>> {
+        let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
+        let mut bytes = FORMAT_MAGIC.to_vec();
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        bytes.extend(to_bytes(ctx, schema)?);
+        Ok(bytes)
+    }
+
+    /// Decodes a schema read from the database, whichever format version it was written with
+    pub fn decode_schema(bytes: &[u8]) -> anyhow::Result<Schema> {
+        let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
+        let Some(rest) = bytes.strip_prefix(FORMAT_MAGIC) else {
+            let schema: SchemaV0 = from_slice(bytes, ctx)?;
+            return Ok(schema.into());
+        };
+        let version = rest
+            .get(..4)
+            .map(|version| u32::from_le_bytes(version.try_into().unwrap()))
+            .ok_or(StorageError::UnknownFormat(0))?;
+        match version {
+            FORMAT_VERSION => Ok(from_slice(&rest[4..], ctx)?),
+            version => Err(StorageError::UnknownFormat(version).into()),
+        }
+    }
+
+    pub async fn get_schema(&self, schema: String) -> DaemonResult<Schema> {
         let schema = self
             .conn
             .fetch_schema(schema)
             .await
             .map_err(into_zbus_error)?;
-        let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
-        let schema: schema::Schema = from_slice(&schema, ctx).map_err(into_zbus_error)?;
-        Ok(schema)
+        Self::decode_schema(&schema).map_err(into_zbus_error)
     }
 
-    pub async fn fetch_all(&self) -> zbus::fdo::Result<Vec<Schema>> {
+    pub async fn fetch_all(&self) -> DaemonResult<Vec<Schema>> {
         self.conn
             .fetch_all()
             .await
             .map(|value| {
                 value
                     .into_iter()
-                    .map(|(_, schema)| {
-                        let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
-                        let schema: schema::Schema = from_slice(&schema, ctx)?;
-                        Ok(schema)
-                    })
+                    .map(|(_, schema)| Self::decode_schema(&schema))
                     .collect::<Result<Vec<Schema>, anyhow::Error>>()
             })
             .map_err(into_zbus_error)?
             .map_err(into_zbus_error)
     }
 
-    pub async fn new_schema(&self, schema: &Schema) -> zbus::fdo::Result<()> {
-        let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
-        let bytes = to_bytes(ctx, schema).map_err(into_zbus_error)?;
+    pub async fn new_schema(&self, schema: &Schema) -> DaemonResult<()> {
+        let bytes = Self::encode_schema(schema).map_err(into_zbus_error)?;
         self.conn
             .new_schema(schema.name().to_string(), bytes)
             .await
             .map_err(into_zbus_error)
     }
 
-    pub async fn update_schema(&self, schema: &Schema) -> zbus::fdo::Result<()> {
-        let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
-        let bytes = to_bytes(ctx, schema).map_err(into_zbus_error)?;
+    /// Inserts `schema` with `owner` as its owner in one transaction
+    pub async fn new_owned_schema(&self, schema: &Schema, owner: &Owner) -> DaemonResult<()> {
+        let bytes = Self::encode_schema(schema).map_err(into_zbus_error)?;
+        self.conn
+            .new_owned_schema(
+                schema.name().to_string(),
+                bytes,
+                owner.app_id.clone(),
+                owner.executable.clone(),
+            )
+            .await
+            .map_err(into_zbus_error)
+    }
+
+    pub async fn update_schema(&self, schema: &Schema) -> DaemonResult<()> {
+        let bytes = Self::encode_schema(schema).map_err(into_zbus_error)?;
 
         self.conn
             .update_schema(schema.name().to_string(), bytes)
@@ -101,11 +264,30 @@ impl Storage {
             .map_err(into_zbus_error)
     }
 
-    pub async fn delete_schema(&self, name: String) -> zbus::fdo::Result<()> {
+    /// Who registered a schema, `None` for schemas registered offline or before owners were
+    /// recorded, which anyone may change
+    pub async fn owner(&self, name: String) -> DaemonResult<Option<Owner>> {
+        let owner = self.conn.fetch_owner(name).await.map_err(into_zbus_error)?;
+        Ok(owner.map(|(app_id, executable)| Owner { app_id, executable }))
+    }
+
+    pub async fn set_owner(&self, name: String, owner: &Owner) -> DaemonResult<()> {
+        self.conn
+            .set_owner(name, owner.app_id.clone(), owner.executable.clone())
+            .await
+            .map_err(into_zbus_error)
+    }
+
+    pub async fn delete_schema(&self, name: String) -> DaemonResult<()> {
         self.conn.delete_schema(name).await.map_err(into_zbus_error)
     }
 }
 
-pub fn into_zbus_error<T: Display>(err: T) -> zbus::fdo::Error {
-    zbus::fdo::Error::Failed(format!("{}", err))
+/// Converts an error to the one the daemon replies with, database failures being `Storage`
+pub fn into_zbus_error(err: impl Into<anyhow::Error>) -> DaemonError {
+    let err = err.into();
+    if err.chain().any(|cause| cause.is::<async_rusqlite::Error>()) {
+        return DaemonError::Storage(format!("{:#}", err));
+    }
+    DaemonError::from(err)
 }
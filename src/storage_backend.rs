@@ -5,8 +5,31 @@ use crate::schema::Schema;
 #[async_trait::async_trait]
 pub trait StorageBackend {
     async fn new_schema(&self, name: String, data: Vec<u8>) -> anyhow::Result<()>;
+    /// Inserts a schema together with its owner, either both or neither are written
+    async fn new_owned_schema(
+        &self,
+        name: String,
+        data: Vec<u8>,
+        app_id: Option<String>,
+        executable: Option<String>,
+    ) -> anyhow::Result<()>;
     async fn update_schema(&self, name: String, data: Vec<u8>) -> anyhow::Result<()>;
     async fn delete_schema(&self, name: String) -> anyhow::Result<()>;
     async fn fetch_all(&self) -> anyhow::Result<Vec<(String, Vec<u8>)>>;
     async fn fetch_schema(&self, name: String) -> anyhow::Result<Vec<u8>>;
+    /// Records who registered a schema, by app id and executable path
+    async fn set_owner(
+        &self,
+        name: String,
+        app_id: Option<String>,
+        executable: Option<String>,
+    ) -> anyhow::Result<()>;
+    async fn fetch_owner(
+        &self,
+        name: String,
+    ) -> anyhow::Result<Option<(Option<String>, Option<String>)>>;
+    /// The value of a counter, 0 if it was never raised
+    async fn fetch_counter(&self, name: String) -> anyhow::Result<u64>;
+    /// Sets a counter to `value`, unless it already holds a greater one
+    async fn raise_counter(&self, name: String, value: u64) -> anyhow::Result<()>;
 }
@@ -1,17 +1,40 @@
 use std::ops::{Deref, DerefMut};
 
-use zvariant::{to_bytes, OwnedSignature, OwnedValue, Signature};
+use zvariant::{to_bytes_for_signature, OwnedSignature, OwnedValue, Signature};
 
 use crate::error::ValueError;
 
-#[derive(
-    serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone, zvariant::Type, zvariant::Value,
-)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, zvariant::Type, zvariant::Value)]
 pub struct Nullable {
     is_null: bool,
     value: OwnedValue,
 }
 
+/// Two nulls are equal whatever placeholder they carry next to `is_null`
+impl PartialEq for Nullable {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.is_null, other.is_null) {
+            (true, true) => true,
+            (false, false) => self.value == other.value,
+            _ => false,
+        }
+    }
+}
+
+impl Nullable {
+    /// Wraps a value the way the daemon expects it, `None` becoming the same null the daemon stores
+    pub fn wrap<T>(value: Option<T>) -> Self
+    where
+        T: Into<zvariant::Value<'static>>,
+    {
+        value.map(|value| OwnedValue::from(value.into())).into()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.is_null
+    }
+}
+
 impl Default for Nullable {
     fn default() -> Self {
         Self {
@@ -116,3 +139,846 @@ impl Into<Nullable> for Value {
         self.value
     }
 }
+
+impl Value {
+    /// Parses `text` in the GVariant text format as a value of signature `sig`.
+    ///
+    /// `nothing` is accepted for every signature and yields a null value, unless `sig` is a maybe type.
+    pub fn parse(text: &str, sig: &Signature<'_>) -> anyhow::Result<Self> {
+        let signature = Signature::from_string_unchecked(sig.to_string());
+        let mut parser = TextParser {
+            text,
+            pos: 0,
+            in_dict: false,
+        };
+
+        if !sig.as_str().starts_with('m') && parser.eat_word("nothing") {
+            parser.end()?;
+            return Ok(Self {
+                signature: signature.into(),
+                value: None.into(),
+            });
+        }
+
+        let value = OwnedValue::from(parser.value(Some(sig.as_str()))?);
+        parser.end()?;
+        Value::check_sig(&value, &signature)?;
+
+        Ok(Self {
+            signature: signature.into(),
+            value: Some(value).into(),
+        })
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl std::fmt::Display for Nullable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_null {
+            true => f.write_str("nothing"),
+            false => f.write_str(&to_text(&self.value, false)),
+        }
+    }
+}
+
+const TYPE_KEYWORDS: [(&str, &str); 13] = [
+    ("boolean", "b"),
+    ("byte", "y"),
+    ("int16", "n"),
+    ("uint16", "q"),
+    ("int32", "i"),
+    ("uint32", "u"),
+    ("handle", "h"),
+    ("int64", "x"),
+    ("uint64", "t"),
+    ("double", "d"),
+    ("string", "s"),
+    ("objectpath", "o"),
+    ("signature", "g"),
+];
+
+/// Splits the first complete type off a signature, `"a{sv}i"` gives `("a{sv}", "i")`
+fn split_type(sig: &str) -> (&str, &str) {
+    let mut depth = 0;
+    for (idx, c) in sig.char_indices() {
+        match c {
+            'a' | 'm' => continue,
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return sig.split_at(idx + 1);
+        }
+    }
+    (sig, "")
+}
+
+fn signature(sig: &str) -> Signature<'static> {
+    Signature::from_string_unchecked(sig.to_string())
+}
+
+struct TextParser<'t> {
+    text: &'t str,
+    pos: usize,
+    /// Set while parsing dictionary entries, which can't hold maybe values, see `dict_entries`
+    in_dict: bool,
+}
+
+impl<'t> TextParser<'t> {
+    fn error(&self, message: impl Into<String>) -> ValueError {
+        ValueError::parse(self.pos, message)
+    }
+
+    fn rest(&self) -> &'t str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.text.len() - trimmed.len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, token: char) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += token.len_utf8();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: char) -> Result<(), ValueError> {
+        match self.eat(token) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected `{}`", token))),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ValueError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("unexpected trailing input")),
+        }
+    }
+
+    fn word(&mut self) -> &'t str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = self.word() == word;
+        if found {
+            self.pos += word.len();
+        }
+        found
+    }
+
+    /// Parses a value of signature `sig`, or infers the type from the text if `sig` is `None`
+    fn value(&mut self, sig: Option<&str>) -> Result<zvariant::Value<'static>, ValueError> {
+        let start = self.pos;
+        let annotation = match self.eat('@') {
+            true => {
+                let rest = self.rest();
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(ValueError::parse(start, "expected a type after `@`"));
+                }
+                self.pos += end;
+                Some(&rest[..end])
+            }
+            false => {
+                let word = self.word();
+                let keyword = TYPE_KEYWORDS.iter().find(|(keyword, _)| word == *keyword);
+                keyword.map(|(keyword, sig)| {
+                    self.pos += keyword.len();
+                    *sig
+                })
+            }
+        };
+        let sig = match (sig, annotation) {
+            (Some(expected), Some(annotated)) if expected != annotated => {
+                return Err(ValueError::parse(
+                    start,
+                    format!(
+                        "type annotation `{}` does not match the expected type `{}`",
+                        annotated, expected
+                    ),
+                ))
+            }
+            (Some(sig), _) | (None, Some(sig)) => Some(sig),
+            (None, None) => None,
+        };
+        if let Some(sig) = sig {
+            if Signature::try_from(sig).is_err() || !split_type(sig).1.is_empty() {
+                return Err(ValueError::parse(start, format!("invalid type `{}`", sig)));
+            }
+        }
+
+        match sig.map(|sig| sig.split_at(1)) {
+            None => self.inferred(),
+            Some(("b", _)) => self.boolean(),
+            Some(("y" | "n" | "q" | "i" | "u" | "x" | "t", _)) => self.integer(sig.unwrap()),
+            Some(("d", _)) => self.double().map(zvariant::Value::F64),
+            Some(("s", _)) => self.string().map(zvariant::Value::from),
+            Some(("o", _)) => {
+                let pos = self.pos;
+                zvariant::ObjectPath::try_from(self.string()?)
+                    .map(zvariant::Value::ObjectPath)
+                    .map_err(|_| ValueError::parse(pos, "invalid object path"))
+            }
+            Some(("g", _)) => {
+                let pos = self.pos;
+                Signature::try_from(self.string()?)
+                    .map(zvariant::Value::Signature)
+                    .map_err(|_| ValueError::parse(pos, "invalid signature"))
+            }
+            Some(("v", _)) => {
+                self.expect('<')?;
+                let inner = self.value(None)?;
+                self.expect('>')?;
+                Ok(zvariant::Value::Value(Box::new(inner)))
+            }
+            Some(("a", element)) if element.starts_with('{') => {
+                let (key, value) = split_type(&element[1..element.len() - 1]);
+                self.dict(Some(key), Some(value))
+            }
+            Some(("a", element)) => self.array(Some(element)),
+            Some(("(", _)) => self.structure(Some(sig.unwrap())),
+            Some(("m", inner)) => self.maybe(Some(inner)),
+            Some((other, _)) => Err(ValueError::parse(
+                start,
+                format!("values of type `{}` are not supported", other),
+            )),
+        }
+    }
+
+    fn inferred(&mut self) -> Result<zvariant::Value<'static>, ValueError> {
+        match self.peek() {
+            Some('\'' | '"') => self.string().map(zvariant::Value::from),
+            Some('<') => self.value(Some("v")),
+            Some('[') => self.array(None),
+            Some('{') => self.dict(None, None),
+            Some('(') => self.structure(None),
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let token = self.number_token();
+                match !token.contains("0x") && token.contains(['.', 'e', 'E']) {
+                    true => self.double().map(zvariant::Value::F64),
+                    false => self.integer("i"),
+                }
+            }
+            _ => match self.word() {
+                "true" | "false" => self.boolean(),
+                "inf" | "nan" => self.double().map(zvariant::Value::F64),
+                "just" | "nothing" => self.maybe(None),
+                "" => Err(self.error("expected a value")),
+                word => Err(self.error(format!("unexpected `{}`", word))),
+            },
+        }
+    }
+
+    fn boolean(&mut self) -> Result<zvariant::Value<'static>, ValueError> {
+        match self.word() {
+            "true" | "false" => {
+                let value = self.word() == "true";
+                self.pos += self.word().len();
+                Ok(zvariant::Value::Bool(value))
+            }
+            _ => Err(self.error("expected `true` or `false`")),
+        }
+    }
+
+    fn number_token(&mut self) -> &'t str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let mut end = 0;
+        for (idx, c) in rest.char_indices() {
+            let after_exponent = idx > 0 && matches!(rest.as_bytes()[idx - 1], b'e' | b'E');
+            let sign = (c == '-' || c == '+') && (idx == 0 || after_exponent);
+            if !(c.is_ascii_alphanumeric() || c == '.' || sign) {
+                break;
+            }
+            end = idx + c.len_utf8();
+        }
+        &rest[..end]
+    }
+
+    fn integer(&mut self, sig: &str) -> Result<zvariant::Value<'static>, ValueError> {
+        let token = self.number_token();
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token.strip_prefix('+').unwrap_or(token)),
+        };
+        let magnitude = match digits.strip_prefix("0x") {
+            Some(hex) => i128::from_str_radix(hex, 16),
+            None => digits.parse::<i128>(),
+        }
+        .map_err(|_| self.error("expected an integer"))?;
+        let number = if negative { -magnitude } else { magnitude };
+
+        let value = match sig {
+            "y" => u8::try_from(number).map(zvariant::Value::U8).ok(),
+            "n" => i16::try_from(number).map(zvariant::Value::I16).ok(),
+            "q" => u16::try_from(number).map(zvariant::Value::U16).ok(),
+            "i" => i32::try_from(number).map(zvariant::Value::I32).ok(),
+            "u" => u32::try_from(number).map(zvariant::Value::U32).ok(),
+            "x" => i64::try_from(number).map(zvariant::Value::I64).ok(),
+            "t" => u64::try_from(number).map(zvariant::Value::U64).ok(),
+            _ => None,
+        }
+        .ok_or(self.error(format!("{} is out of range for type `{}`", token, sig)))?;
+        self.pos += token.len();
+        Ok(value)
+    }
+
+    fn double(&mut self) -> Result<f64, ValueError> {
+        let token = match self.word() {
+            "inf" | "nan" => self.word(),
+            _ => self.number_token(),
+        };
+        let value = match token {
+            "inf" => f64::INFINITY,
+            "-inf" => f64::NEG_INFINITY,
+            "nan" => f64::NAN,
+            _ => token
+                .parse()
+                .map_err(|_| self.error("expected a floating point number"))?,
+        };
+        self.pos += token.len();
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, ValueError> {
+        let quote = match self.peek() {
+            Some(quote @ ('\'' | '"')) => quote,
+            _ => return Err(self.error("expected a quoted string")),
+        };
+        let start = self.pos;
+        let mut string = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((idx, c)) = chars.next() {
+            if c == quote {
+                self.pos += idx + 1;
+                return Ok(string);
+            }
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some((_, 'n')) => '\n',
+                Some((_, 't')) => '\t',
+                Some((_, 'r')) => '\r',
+                Some((_, escape @ ('u' | 'U'))) => {
+                    let digits = if escape == 'u' { 4 } else { 8 };
+                    let hex: String = chars.by_ref().take(digits).map(|(_, c)| c).collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or(ValueError::parse(start + idx, "invalid unicode escape"))?
+                }
+                Some((_, other)) => other,
+                None => break,
+            };
+            string.push(escaped);
+        }
+        Err(ValueError::parse(start, "unterminated string"))
+    }
+
+    fn array(&mut self, element: Option<&str>) -> Result<zvariant::Value<'static>, ValueError> {
+        self.expect('[')?;
+        let mut element = element.map(str::to_string);
+        let mut values = vec![];
+        while !self.eat(']') {
+            if !values.is_empty() {
+                self.expect(',')?;
+            }
+            let value = self.value(element.as_deref())?;
+            element.get_or_insert_with(|| value.value_signature().to_string());
+            values.push(value);
+        }
+
+        let element = element.ok_or(
+            self.error("cannot infer the type of an empty array, annotate it like `@as []`"),
+        )?;
+        let mut array = zvariant::Array::new(signature(&element));
+        for value in values {
+            array
+                .append(value)
+                .map_err(|err| self.error(err.to_string()))?;
+        }
+        Ok(zvariant::Value::Array(array))
+    }
+
+    /// Parses either a dictionary (`{'k': 1, ...}`) or an array of dict entries (`[{'k', 1}, ...]`)
+    fn dict(
+        &mut self,
+        key: Option<&str>,
+        value: Option<&str>,
+    ) -> Result<zvariant::Value<'static>, ValueError> {
+        let (open, close, separator) = match self.peek() {
+            Some('[') if key.is_some() => ('[', ']', ','),
+            _ => ('{', '}', ':'),
+        };
+        self.expect(open)?;
+        let in_dict = std::mem::replace(&mut self.in_dict, true);
+
+        let mut sigs = (key.map(str::to_string), value.map(str::to_string));
+        let mut entries = vec![];
+        while !self.eat(close) {
+            if !entries.is_empty() {
+                self.expect(',')?;
+            }
+            if open == '[' {
+                self.expect('{')?;
+            }
+            let key = self.value(sigs.0.as_deref())?;
+            self.expect(separator)?;
+            let value = self.value(sigs.1.as_deref())?;
+            if open == '[' {
+                self.expect('}')?;
+            }
+            sigs.0
+                .get_or_insert_with(|| key.value_signature().to_string());
+            sigs.1
+                .get_or_insert_with(|| value.value_signature().to_string());
+            entries.push((key, value));
+        }
+        self.in_dict = in_dict;
+
+        let (Some(key), Some(value)) = sigs else {
+            return Err(self.error(
+                "cannot infer the type of an empty dictionary, annotate it like `@a{sv} {}`",
+            ));
+        };
+        let mut dict = zvariant::Dict::new(signature(&key), signature(&value));
+        for (key, value) in entries {
+            dict.append(key, value)
+                .map_err(|err| self.error(err.to_string()))?;
+        }
+        Ok(zvariant::Value::Dict(dict))
+    }
+
+    fn structure(&mut self, sig: Option<&str>) -> Result<zvariant::Value<'static>, ValueError> {
+        self.expect('(')?;
+        let mut fields = sig.map(|sig| &sig[1..sig.len() - 1]);
+        let mut builder = zvariant::StructureBuilder::new();
+        let mut count = 0;
+        loop {
+            let done = match fields {
+                Some(fields) => fields.is_empty(),
+                None => self.peek() == Some(')'),
+            };
+            if done {
+                // Single element tuples are written `(1,)`.
+                if count == 1 {
+                    self.eat(',');
+                }
+                break;
+            }
+            if count > 0 {
+                self.expect(',')?;
+                if fields.is_none() && count == 1 && self.peek() == Some(')') {
+                    break;
+                }
+            }
+            let field = fields.map(split_type);
+            builder = builder.append_field(self.value(field.map(|(field, _)| field))?);
+            fields = field.map(|(_, rest)| rest);
+            count += 1;
+        }
+        self.expect(')')?;
+
+        match count {
+            0 => Err(self.error("empty tuples are not supported")),
+            _ => Ok(zvariant::Value::Structure(builder.build())),
+        }
+    }
+
+    fn maybe(&mut self, inner: Option<&str>) -> Result<zvariant::Value<'static>, ValueError> {
+        if self.in_dict {
+            self.skip_whitespace();
+            return Err(self.error("maybe values are not supported inside dictionaries"));
+        }
+        if self.eat_word("nothing") {
+            return match inner {
+                Some(inner) => Ok(zvariant::Value::Maybe(zvariant::Maybe::nothing(signature(
+                    inner,
+                )))),
+                None => Err(self
+                    .error("cannot infer the type of `nothing`, annotate it like `@ms nothing`")),
+            };
+        }
+        // `just` can be left out, unless the inner type is a maybe itself.
+        let explicit = self.eat_word("just");
+        if !explicit && inner.filter(|inner| !inner.starts_with('m')).is_none() {
+            return Err(self.error("expected `just` or `nothing`"));
+        }
+        Ok(zvariant::Value::Maybe(zvariant::Maybe::just(
+            self.value(inner)?,
+        )))
+    }
+}
+
+/// Prints `value` in the GVariant text format. With `annotate`, the type is prefixed wherever
+/// it could not be inferred back from the text, as needed inside variants.
+fn to_text(value: &zvariant::Value<'_>, annotate: bool) -> String {
+    use zvariant::Value as V;
+
+    let text = match value {
+        V::Bool(value) => value.to_string(),
+        V::U8(value) => value.to_string(),
+        V::I16(value) => value.to_string(),
+        V::U16(value) => value.to_string(),
+        V::I32(value) => value.to_string(),
+        V::U32(value) => value.to_string(),
+        V::I64(value) => value.to_string(),
+        V::U64(value) => value.to_string(),
+        V::F64(value) if value.is_nan() => "nan".to_string(),
+        V::F64(value) => {
+            let text = value.to_string();
+            match text.contains(['.', 'e', 'i']) {
+                true => text,
+                false => format!("{}.0", text),
+            }
+        }
+        V::Str(value) => quote(value.as_str()),
+        V::ObjectPath(value) => quote(value.as_str()),
+        V::Signature(value) => quote(value.as_str()),
+        V::Value(inner) => format!("<{}>", to_text(inner, true)),
+        V::Array(array) => {
+            let elements: Vec<_> = array.get().iter().map(|v| to_text(v, false)).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        V::Dict(dict) => {
+            let entries: Vec<_> = dict_entries(dict)
+                .iter()
+                .map(|(key, value)| format!("{}: {}", to_text(key, false), to_text(value, false)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        V::Structure(structure) => {
+            let fields: Vec<_> = structure
+                .fields()
+                .iter()
+                .map(|v| to_text(v, false))
+                .collect();
+            match fields.len() {
+                1 => format!("({},)", fields[0]),
+                _ => format!("({})", fields.join(", ")),
+            }
+        }
+        V::Maybe(maybe) => match maybe.inner() {
+            None => "nothing".to_string(),
+            Some(inner) => format!("just {}", to_text(inner, false)),
+        },
+        #[cfg(unix)]
+        V::Fd(fd) => fd.to_string(),
+    };
+
+    if !annotate {
+        return text;
+    }
+    let sig = value.value_signature();
+    let mut parser = TextParser {
+        text: &text,
+        pos: 0,
+        in_dict: false,
+    };
+    let inferred = parser
+        .value(None)
+        .ok()
+        .filter(|_| parser.end().is_ok())
+        .map(|value| value.value_signature() == sig)
+        .unwrap_or(false);
+    match TYPE_KEYWORDS.iter().find(|(_, s)| *s == sig.as_str()) {
+        _ if inferred => text,
+        Some((keyword, _)) => format!("{} {}", keyword, text),
+        None => format!("@{} {}", sig, text),
+    }
+}
+
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('\'');
+    for c in string.chars() {
+        match c {
+            '\'' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// zvariant does not expose the entries of a `Dict` by reference. Dict entries and structures
+/// share their encoding, so the dict is encoded as an array of `(key, value)` structures and read
+/// back as one. D-Bus can't encode maybe types, the parsers reject them inside dictionaries.
+fn dict_entries(dict: &zvariant::Dict<'_, '_>) -> Vec<(OwnedValue, OwnedValue)> {
+    let ctx = zvariant::EncodingContext::<byteorder::LE>::new_dbus(0);
+    let dict_sig = dict.signature();
+    let entries_sig = signature(&format!("a({})", &dict_sig[2..dict_sig.len() - 1]));
+    let Ok(bytes) = to_bytes_for_signature(ctx, &entries_sig, dict) else {
+        return vec![];
+    };
+
+    let Ok(array) = zvariant::from_slice_for_dynamic_signature::<_, zvariant::Array<'_>>(
+        &bytes,
+        ctx,
+        &entries_sig,
+    ) else {
+        return vec![];
+    };
+    array
+        .get()
+        .iter()
+        .filter_map(|entry| match entry {
+            zvariant::Value::Structure(entry) => match entry.fields() {
+                [key, value] => Some((key.to_owned(), value.to_owned())),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(feature = "json")]
+impl Value {
+    /// Converts the value into natural JSON: structures become arrays, dictionaries become objects
+    /// and null values become `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.value.to_json()
+    }
+
+    /// Builds a value of signature `sig` from JSON, the inverse of `Value::to_json`.
+    ///
+    /// `null` yields a null value, unless `sig` is a maybe type. Variants are inferred from the JSON
+    /// type: booleans, `int32`/`int64`/`uint64` integers, doubles, strings, `av` and `a{sv}`.
+    pub fn from_json(json: &serde_json::Value, sig: &Signature<'_>) -> anyhow::Result<Self> {
+        let signature = Signature::from_string_unchecked(sig.to_string());
+        if json.is_null() && !sig.as_str().starts_with('m') {
+            return Ok(Self {
+                signature: signature.into(),
+                value: None.into(),
+            });
+        }
+
+        let value = OwnedValue::from(from_json(json, Some(sig.as_str()), "$")?);
+        Value::check_sig(&value, &signature)?;
+        Ok(Self {
+            signature: signature.into(),
+            value: Some(value).into(),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl Nullable {
+    pub fn to_json(&self) -> serde_json::Value {
+        match self.is_null {
+            true => serde_json::Value::Null,
+            false => to_json(&self.value),
+        }
+    }
+}
+
+/// Converts any zvariant value into JSON, see `Value::to_json`
+#[cfg(feature = "json")]
+pub fn to_json(value: &zvariant::Value<'_>) -> serde_json::Value {
+    use serde_json::Value as J;
+    use zvariant::Value as V;
+
+    match value {
+        V::Bool(value) => J::from(*value),
+        V::U8(value) => J::from(*value),
+        V::I16(value) => J::from(*value),
+        V::U16(value) => J::from(*value),
+        V::I32(value) => J::from(*value),
+        V::U32(value) => J::from(*value),
+        V::I64(value) => J::from(*value),
+        V::U64(value) => J::from(*value),
+        // Infinity and NaN have no JSON representation and become `null`.
+        V::F64(value) => J::from(*value),
+        V::Str(value) => J::from(value.as_str()),
+        V::ObjectPath(value) => J::from(value.as_str()),
+        V::Signature(value) => J::from(value.as_str()),
+        V::Value(inner) => to_json(inner),
+        V::Array(array) => J::Array(array.get().iter().map(to_json).collect()),
+        V::Dict(dict) => J::Object(
+            dict_entries(dict)
+                .iter()
+                .map(|(key, value)| {
+                    let key = match to_json(key) {
+                        J::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, to_json(value))
+                })
+                .collect(),
+        ),
+        V::Structure(structure) => J::Array(structure.fields().iter().map(to_json).collect()),
+        V::Maybe(maybe) => maybe.inner().as_ref().map(to_json).unwrap_or(J::Null),
+        #[cfg(unix)]
+        V::Fd(fd) => J::from(std::os::unix::io::AsRawFd::as_raw_fd(fd)),
+    }
+}
+
+#[cfg(feature = "json")]
+fn from_json(
+    json: &serde_json::Value,
+    sig: Option<&str>,
+    path: &str,
+) -> Result<zvariant::Value<'static>, ValueError> {
+    use serde_json::Value as J;
+    use zvariant::Value as V;
+
+    let mismatch = |expected: &str| {
+        ValueError::Json(
+            path.to_string(),
+            format!("expected {}, found `{}`", expected, json),
+        )
+    };
+    let integer = |sig: &str| -> Result<V<'static>, ValueError> {
+        let value = match (json.as_i64(), json.as_u64()) {
+            (Some(number), _) => number as i128,
+            (None, Some(number)) => number as i128,
+            _ => return Err(mismatch("an integer")),
+        };
+        match sig {
+            "y" => u8::try_from(value).map(V::U8).ok(),
+            "n" => i16::try_from(value).map(V::I16).ok(),
+            "q" => u16::try_from(value).map(V::U16).ok(),
+            "i" => i32::try_from(value).map(V::I32).ok(),
+            "u" => u32::try_from(value).map(V::U32).ok(),
+            "x" => i64::try_from(value).map(V::I64).ok(),
+            "t" => u64::try_from(value).map(V::U64).ok(),
+            _ => None,
+        }
+        .ok_or(ValueError::Json(
+            path.to_string(),
+            format!("{} is out of range for type `{}`", value, sig),
+        ))
+    };
+    let string = || {
+        json.as_str()
+            .map(str::to_string)
+            .ok_or(mismatch("a string"))
+    };
+
+    let Some(sig) = sig else {
+        // Variants carry no type information in JSON, so it is inferred.
+        let sig = match json {
+            J::Null => return Err(mismatch("a value")),
+            J::Bool(_) => "b",
+            J::Number(number) if number.is_f64() => "d",
+            J::Number(number) if number.as_i64().is_some_and(|n| i32::try_from(n).is_ok()) => "i",
+            J::Number(number) if number.is_i64() => "x",
+            J::Number(_) => "t",
+            J::String(_) => "s",
+            J::Array(_) => "av",
+            J::Object(_) => "a{sv}",
+        };
+        return from_json(json, Some(sig), path);
+    };
+
+    match sig.split_at(1) {
+        ("b", _) => json.as_bool().map(V::Bool).ok_or(mismatch("a boolean")),
+        ("y" | "n" | "q" | "i" | "u" | "x" | "t", _) => integer(sig),
+        ("d", _) => json.as_f64().map(V::F64).ok_or(mismatch("a number")),
+        ("s", _) => string().map(V::from),
+        ("o", _) => zvariant::ObjectPath::try_from(string()?)
+            .map(V::ObjectPath)
+            .map_err(|_| mismatch("an object path")),
+        ("g", _) => Signature::try_from(string()?)
+            .map(V::Signature)
+            .map_err(|_| mismatch("a signature")),
+        ("v", _) => Ok(V::Value(Box::new(from_json(json, None, path)?))),
+        ("a", element) if element.starts_with('{') => {
+            let (key_sig, value_sig) = split_type(&element[1..element.len() - 1]);
+            if value_sig.contains('m') {
+                return Err(ValueError::Json(
+                    path.to_string(),
+                    "maybe values are not supported inside dictionaries".to_string(),
+                ));
+            }
+            let object = json.as_object().ok_or(mismatch("an object"))?;
+            let mut dict = zvariant::Dict::new(signature(key_sig), signature(value_sig));
+            for (key, value) in object {
+                let path = format!("{}.{}", path, key);
+                // Object keys are always strings, other key types are parsed from them.
+                let key = match key_sig {
+                    "s" | "o" | "g" => from_json(&J::from(key.as_str()), Some(key_sig), &path)?,
+                    _ => TextParser {
+                        text: key,
+                        pos: 0,
+                        in_dict: true,
+                    }
+                    .value(Some(key_sig))
+                    .map_err(|err| ValueError::Json(path.clone(), err.to_string()))?,
+                };
+                let value = from_json(value, Some(value_sig), &path)?;
+                dict.append(key, value)
+                    .map_err(|err| ValueError::Json(path.clone(), err.to_string()))?;
+            }
+            Ok(V::Dict(dict))
+        }
+        ("a", element) => {
+            let items = json.as_array().ok_or(mismatch("an array"))?;
+            let mut array = zvariant::Array::new(signature(element));
+            for (idx, item) in items.iter().enumerate() {
+                let path = format!("{}[{}]", path, idx);
+                array
+                    .append(from_json(item, Some(element), &path)?)
+                    .map_err(|err| ValueError::Json(path, err.to_string()))?;
+            }
+            Ok(V::Array(array))
+        }
+        ("(", fields) => {
+            let items = json.as_array().ok_or(mismatch("an array"))?;
+            let mut fields = &fields[..fields.len() - 1];
+            let mut builder = zvariant::StructureBuilder::new();
+            for (idx, item) in items.iter().enumerate() {
+                if fields.is_empty() {
+                    return Err(mismatch(&format!("{} fields", idx)));
+                }
+                let (field, rest) = split_type(fields);
+                let path = format!("{}[{}]", path, idx);
+                builder = builder.append_field(from_json(item, Some(field), &path)?);
+                fields = rest;
+            }
+            if !fields.is_empty() {
+                return Err(mismatch(&format!("a tuple of type `{}`", sig)));
+            }
+            Ok(V::Structure(builder.build()))
+        }
+        ("m", inner) => Ok(V::Maybe(match json {
+            J::Null => zvariant::Maybe::nothing(signature(inner)),
+            json => zvariant::Maybe::just(from_json(json, Some(inner), path)?),
+        })),
+        (other, _) => Err(ValueError::Json(
+            path.to_string(),
+            format!("values of type `{}` are not supported", other),
+        )),
+    }
+}